@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::instruction::{Instruction, OpcodeInfo, Operand};
+
+//Assembler for the SM83 syntax of the opcode tables, so tests can be written as source:
+//
+//  start:  LD A,$12        ; values in hex ($12, 0x12), decimal or binary (%1010)
+//          LDH ($44),A     ; 0xFF00 page, LD ($FF44),A is the 3 byte form
+//          JR NZ,start
+//          db 1, 2, "text"
+//          dw start
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub labels: HashMap<String, u16>,
+}
+
+impl Program {
+    pub fn label(&self, name: &str) -> Option<u16> {
+        self.labels.get(name).copied()
+    }
+}
+
+//Operand as written in the source
+#[derive(Debug, Clone)]
+enum Arg {
+    //Register, register indirection or condition
+    Exact(Operand),
+    Value(String),
+    Indirect(String),
+    SpOffset(String),
+}
+
+enum Statement {
+    Instruction(bool, u8, &'static OpcodeInfo, Vec<Arg>),
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+pub fn assemble(source: &str, origin: u16) -> Result<Program, AsmError> {
+    //First pass: sizes and label addresses
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = origin as u32;
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| AsmError { line: number + 1, message };
+        let mut line = strip_comment(line).trim();
+        while let Some((label, rest)) = split_label(line) {
+            if labels.insert(label.to_string(), address as u16).is_some() {
+                return Err(error(format!("label '{}' defined twice", label)));
+            }
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = split_args(rest);
+        let statement = match mnemonic.to_ascii_uppercase().as_str() {
+            "DB" => {
+                let mut size = 0;
+                for arg in &args {
+                    size += match parse_string(arg) {
+                        Some(text) => text.map_err(error)?.len(),
+                        None => 1,
+                    };
+                }
+                address += size as u32;
+                Statement::Bytes(args)
+            }
+            "DW" => {
+                address += 2 * args.len() as u32;
+                Statement::Words(args)
+            }
+            mnemonic => {
+                let parsed: Vec<Arg> = args.iter().map(|a| parse_arg(a)).collect();
+                let (prefixed, opcode, info) = find_opcode(mnemonic, &parsed, &labels).map_err(error)?;
+                address += info.length as u32;
+                Statement::Instruction(prefixed, opcode, info, parsed)
+            }
+        };
+        if address > 0x10000 {
+            return Err(error("program goes past 0xFFFF".to_string()));
+        }
+        statements.push((number + 1, statement));
+    }
+
+    //Second pass: every label is known, emit the bytes
+    let mut bytes = Vec::new();
+    for (line, statement) in statements {
+        let error = |message: String| AsmError { line, message };
+        let eval = |expr: &str| evaluate(expr, &labels).map_err(error);
+        match statement {
+            Statement::Bytes(args) => {
+                for arg in &args {
+                    match parse_string(arg) {
+                        Some(text) => bytes.extend_from_slice(text.map_err(error)?.as_bytes()),
+                        None => bytes.push(byte_value(eval(arg)?).map_err(error)?),
+                    }
+                }
+            }
+            Statement::Words(args) => {
+                for arg in &args {
+                    bytes.extend_from_slice(&(eval(arg)? as u16).to_le_bytes());
+                }
+            }
+            Statement::Instruction(prefixed, opcode, info, args) => {
+                let pc = origin.wrapping_add(bytes.len() as u16);
+                if prefixed {
+                    bytes.push(0xCB);
+                }
+                bytes.push(opcode);
+                for (operand, arg) in info.operands().zip(&args) {
+                    let expr = match (operand, arg) {
+                        //Bit numbers and RST vectors are part of the opcode
+                        (Operand::Bit(_) | Operand::Rst(_), _) | (_, Arg::Exact(_)) => continue,
+                        (_, Arg::Value(e) | Arg::Indirect(e) | Arg::SpOffset(e)) => e,
+                    };
+                    let value = eval(expr)?;
+                    match operand {
+                        Operand::D8 => bytes.push(byte_value(value).map_err(error)?),
+                        Operand::A8 => bytes.push(high_page(value).map_err(error)?),
+                        Operand::D16 | Operand::A16 | Operand::IndA16 => {
+                            bytes.extend_from_slice(&(value as u16).to_le_bytes())
+                        }
+                        Operand::R8 if matches!(info.instruction, Some(Instruction::JR(_))) => {
+                            let offset = value - (pc as i32 + 2);
+                            bytes.push(signed_value(offset).map_err(|_| {
+                                error(format!("jump target ${:04X} is out of range of JR", value))
+                            })?);
+                        }
+                        Operand::R8 | Operand::SPR8 => bytes.push(signed_value(value).map_err(error)?),
+                        _ => unreachable!(),
+                    }
+                }
+                //STOP is followed by a padding byte
+                if matches!(info.instruction, Some(Instruction::STOP())) {
+                    bytes.push(0x00);
+                }
+            }
+        }
+    }
+    Ok(Program { origin, bytes, labels })
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    let valid = !label.is_empty()
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && !label.starts_with(|c: char| c.is_ascii_digit());
+    valid.then_some((label, rest))
+}
+
+//Split on commas outside of strings
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            ',' if !in_string => args.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !args.is_empty() {
+        args.push(current);
+    }
+    args.into_iter().map(|a| a.trim().to_string()).collect()
+}
+
+fn parse_string(arg: &str) -> Option<Result<&str, String>> {
+    let text = arg.strip_prefix('"')?;
+    Some(text.strip_suffix('"').ok_or(format!("unterminated string {}", arg)))
+}
+
+fn parse_arg(arg: &str) -> Arg {
+    let upper = arg.to_ascii_uppercase().replace(' ', "");
+    let exact = match upper.as_str() {
+        "A" => Some(Operand::A),
+        "B" => Some(Operand::B),
+        "C" => Some(Operand::C),
+        "D" => Some(Operand::D),
+        "E" => Some(Operand::E),
+        "H" => Some(Operand::H),
+        "L" => Some(Operand::L),
+        "AF" => Some(Operand::AF),
+        "BC" => Some(Operand::BC),
+        "DE" => Some(Operand::DE),
+        "HL" => Some(Operand::HL),
+        "SP" => Some(Operand::SP),
+        "NZ" => Some(Operand::CondNZ),
+        "Z" => Some(Operand::CondZ),
+        "NC" => Some(Operand::CondNC),
+        "(BC)" => Some(Operand::IndBC),
+        "(DE)" => Some(Operand::IndDE),
+        "(HL)" => Some(Operand::IndHL),
+        "(HL+)" | "(HLI)" => Some(Operand::IndHLI),
+        "(HL-)" | "(HLD)" => Some(Operand::IndHLD),
+        "(C)" | "($FF00+C)" | "(0XFF00+C)" => Some(Operand::IndC),
+        _ => None,
+    };
+    if let Some(operand) = exact {
+        return Arg::Exact(operand);
+    }
+    if let Some(inner) = arg.strip_prefix('(').and_then(|a| a.strip_suffix(')')) {
+        return Arg::Indirect(inner.trim().to_string());
+    }
+    if upper.starts_with("SP+") || upper.starts_with("SP-") {
+        return Arg::SpOffset(arg.trim()[2..].trim().to_string());
+    }
+    Arg::Value(arg.to_string())
+}
+
+fn accepts(operand: Operand, arg: &Arg, mnemonic: &str, labels: &HashMap<String, u16>) -> bool {
+    match (operand, arg) {
+        //C is both a register and a condition
+        (Operand::CondC, Arg::Exact(Operand::C)) => true,
+        (Operand::IndHL, Arg::Exact(Operand::HL)) => mnemonic == "JP",
+        (operand, Arg::Exact(exact)) => operand == *exact,
+        (Operand::D8 | Operand::D16 | Operand::A16 | Operand::R8, Arg::Value(_)) => true,
+        (Operand::Bit(bit), Arg::Value(e)) => evaluate(e, labels) == Ok(bit as i32),
+        (Operand::Rst(vector), Arg::Value(e)) => {
+            //RST 38H, the notation of the opcode tables
+            let e = e.strip_suffix(['h', 'H']).map(|hex| format!("${}", hex)).unwrap_or(e.clone());
+            evaluate(&e, labels) == Ok(vector as i32)
+        }
+        (Operand::IndA16, Arg::Indirect(_)) => mnemonic != "LDH",
+        (Operand::A8, Arg::Indirect(_)) => mnemonic == "LDH",
+        (Operand::SPR8, Arg::SpOffset(_)) => true,
+        _ => false,
+    }
+}
+
+fn find_opcode(
+    mnemonic: &str,
+    args: &[Arg],
+    labels: &HashMap<String, u16>,
+) -> Result<(bool, u8, &'static OpcodeInfo), String> {
+    let mut known = false;
+    for prefixed in [false, true] {
+        for opcode in 0..=255u8 {
+            let info = OpcodeInfo::get(opcode, prefixed);
+            if info.instruction.is_none() || info.mnemonic != mnemonic || (!prefixed && opcode == 0xCB) {
+                continue;
+            }
+            known = true;
+            let operands: Vec<Operand> = info.operands().collect();
+            if operands.len() == args.len()
+                && operands.iter().zip(args).all(|(&o, a)| accepts(o, a, mnemonic, labels))
+            {
+                return Ok((prefixed, opcode, info));
+            }
+        }
+    }
+    if known {
+        Err(format!("invalid operands for {}", mnemonic))
+    } else {
+        Err(format!("unknown instruction '{}'", mnemonic))
+    }
+}
+
+//Sum of numbers and labels: "table+2", "end-start"
+fn evaluate(expr: &str, labels: &HashMap<String, u16>) -> Result<i32, String> {
+    let expr = expr.replace(' ', "");
+    if expr.is_empty() {
+        return Err("missing value".to_string());
+    }
+    let mut total = 0i32;
+    let mut rest = expr.as_str();
+    let mut sign = 1;
+    if let Some(r) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = r;
+    } else if let Some(r) = rest.strip_prefix('+') {
+        rest = r;
+    }
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = &rest[..end];
+        let value = parse_number(term)
+            .or_else(|| labels.get(term).map(|&v| v as i32))
+            .ok_or(format!("unknown value '{}'", term))?;
+        total += sign * value;
+        if end == rest.len() {
+            return Ok(total);
+        }
+        sign = if rest.as_bytes()[end] == b'-' { -1 } else { 1 };
+        rest = &rest[end + 1..];
+    }
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (digits, radix) = if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        (hex, 16)
+    } else if let Some(binary) = text.strip_prefix('%').or_else(|| text.strip_prefix("0b")) {
+        (binary, 2)
+    } else {
+        (text, 10)
+    };
+    i32::from_str_radix(digits, radix).ok()
+}
+
+fn byte_value(value: i32) -> Result<u8, String> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in a byte", value)),
+    }
+}
+
+fn signed_value(value: i32) -> Result<u8, String> {
+    match value {
+        -128..=127 => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in a signed byte", value)),
+    }
+}
+
+//LDH takes either the offset or the full 0xFF00-0xFFFF address
+fn high_page(value: i32) -> Result<u8, String> {
+    match value {
+        0..=0xFF => Ok(value as u8),
+        0xFF00..=0xFFFF => Ok((value & 0xFF) as u8),
+        _ => Err(format!("${:04X} is outside of the 0xFF00 page", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+    use crate::disasm;
+
+    #[test]
+    fn assembles_instructions_and_labels() {
+        let program = assemble(
+            "start:  LD A,$12   ; comment
+                     LDH ($44),A
+                     ld hl,data
+             loop:   DEC A
+                     JR NZ,loop
+                     JP C,start
+                     BIT 7,(HL)
+                     RST 38H
+                     ADD SP,-2
+                     LD HL,SP+4
+                     LD (HL+),A
+             data:   db 1, \"ok\", -1
+                     dw data+1",
+            0x0150,
+        )
+        .unwrap();
+        assert_eq!(
+            program.bytes,
+            [
+                0x3E, 0x12, 0xE0, 0x44, 0x21, 0x65, 0x01, 0x3D, 0x20, 0xFD, 0xDA, 0x50, 0x01, 0xCB, 0x7E, 0xFF,
+                0xE8, 0xFE, 0xF8, 0x04, 0x22, 0x01, b'o', b'k', 0xFF, 0x66, 0x01,
+            ]
+        );
+        assert_eq!(program.label("loop"), Some(0x0157));
+    }
+
+    #[test]
+    fn disassembly_assembles_back() {
+        let code = [0x01, 0x34, 0x12, 0xFA, 0x00, 0xC0, 0xE2, 0xCB, 0x37, 0xC4, 0x00, 0x02, 0x10, 0x00, 0x76];
+        let read = |address: u16| code.get(address as usize).copied().unwrap_or(0);
+        let source: Vec<String> = disasm::linear(read, 0, 7).into_iter().map(|l| l.text).collect();
+        assert_eq!(assemble(&source.join("\n"), 0).unwrap().bytes, code);
+    }
+
+    #[test]
+    fn program_runs_on_flat_cpu() {
+        let program = assemble(
+            "       LD B,5
+                    XOR A
+             loop:  ADD A,B
+                    DEC B
+                    JR NZ,loop
+                    LD ($C000),A
+             done:  JR done",
+            0x0200,
+        )
+        .unwrap();
+        let mut cpu = CPU::flat();
+        cpu.load_program(&program);
+        while cpu.pc() != program.label("done").unwrap() {
+            cpu.step();
+        }
+        assert_eq!(cpu.bus.bus_read(0xC000), 15);
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = |source| assemble(source, 0).err().map(|e| e.to_string());
+        assert_eq!(error("NOP\nFOO A"), Some("line 2: unknown instruction 'FOO'".to_string()));
+        assert_eq!(error("LD (BC),B"), Some("line 1: invalid operands for LD".to_string()));
+        assert_eq!(error("JP nowhere"), Some("line 1: unknown value 'nowhere'".to_string()));
+        assert_eq!(error("x: NOP\nx: NOP"), Some("line 2: label 'x' defined twice".to_string()));
+        assert!(error("JR far\nds: db 0\nfar: NOP").is_none());
+    }
+}
@@ -0,0 +1,352 @@
+use std::fmt;
+use std::io;
+use log::warn;
+
+//Header layout (0x0100 - 0x014F)
+const TITLE_START: usize = 0x0134;
+const TITLE_END: usize = 0x0144;
+const CGB_FLAG: usize = 0x0143;
+const NEW_LICENSEE_CODE: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+const RAM_SIZE: usize = 0x0149;
+const OLD_LICENSEE_CODE: usize = 0x014B;
+const VERSION: usize = 0x014C;
+const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E;
+const HEADER_END: usize = 0x0150;
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    TooSmall(usize),
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    HeaderChecksum { expected: u8, computed: u8 },
+    UnsupportedMbc(MbcKind),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Io(e) => write!(f, "couldn't read the ROM file: {}", e),
+            CartridgeError::TooSmall(size) => {
+                write!(f, "ROM is too small to contain a cartridge header ({} bytes)", size)
+            }
+            CartridgeError::UnknownCartridgeType(code) => write!(f, "unknown cartridge type 0x{:02X}", code),
+            CartridgeError::UnknownRomSize(code) => write!(f, "unknown ROM size code 0x{:02X}", code),
+            CartridgeError::UnknownRamSize(code) => write!(f, "unknown RAM size code 0x{:02X}", code),
+            CartridgeError::HeaderChecksum { expected, computed } => {
+                write!(f, "header checksum mismatch (header: 0x{:02X}, computed: 0x{:02X})", expected, computed)
+            }
+            CartridgeError::UnsupportedMbc(kind) => write!(f, "unsupported memory bank controller {:?}", kind),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> CartridgeError {
+        CartridgeError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    DmgOnly,
+    CgbCompatible,
+    CgbOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MbcKind {
+    None,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    Mmm01,
+    PocketCamera,
+    Tama5,
+    HuC1,
+    HuC3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mbc: MbcKind,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Option<CartridgeType> {
+        //(mbc, ram, battery, timer, rumble)
+        let (mbc, ram, battery, timer, rumble) = match code {
+            0x00 => (MbcKind::None, false, false, false, false),
+            0x01 => (MbcKind::Mbc1, false, false, false, false),
+            0x02 => (MbcKind::Mbc1, true, false, false, false),
+            0x03 => (MbcKind::Mbc1, true, true, false, false),
+            0x05 => (MbcKind::Mbc2, false, false, false, false),
+            0x06 => (MbcKind::Mbc2, false, true, false, false),
+            0x08 => (MbcKind::None, true, false, false, false),
+            0x09 => (MbcKind::None, true, true, false, false),
+            0x0B => (MbcKind::Mmm01, false, false, false, false),
+            0x0C => (MbcKind::Mmm01, true, false, false, false),
+            0x0D => (MbcKind::Mmm01, true, true, false, false),
+            0x0F => (MbcKind::Mbc3, false, true, true, false),
+            0x10 => (MbcKind::Mbc3, true, true, true, false),
+            0x11 => (MbcKind::Mbc3, false, false, false, false),
+            0x12 => (MbcKind::Mbc3, true, false, false, false),
+            0x13 => (MbcKind::Mbc3, true, true, false, false),
+            0x19 => (MbcKind::Mbc5, false, false, false, false),
+            0x1A => (MbcKind::Mbc5, true, false, false, false),
+            0x1B => (MbcKind::Mbc5, true, true, false, false),
+            0x1C => (MbcKind::Mbc5, false, false, false, true),
+            0x1D => (MbcKind::Mbc5, true, false, false, true),
+            0x1E => (MbcKind::Mbc5, true, true, false, true),
+            0x20 => (MbcKind::Mbc6, true, true, false, false),
+            0x22 => (MbcKind::Mbc7, true, true, false, true),
+            0xFC => (MbcKind::PocketCamera, true, true, false, false),
+            0xFD => (MbcKind::Tama5, true, true, false, false),
+            0xFE => (MbcKind::HuC3, true, true, true, false),
+            0xFF => (MbcKind::HuC1, true, true, false, false),
+            _ => return None,
+        };
+        Some(CartridgeType { code, mbc, ram, battery, timer, rumble })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Licensee {
+    //Code stored at 0x014B
+    Old(u8),
+    //Two ASCII characters stored at 0x0144-0x0145, used when 0x014B is 0x33
+    New([u8; 2]),
+}
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub licensee: Licensee,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, CartridgeError> {
+        if rom.len() < HEADER_END {
+            return Err(CartridgeError::TooSmall(rom.len()));
+        }
+
+        let cgb = match rom[CGB_FLAG] {
+            0xC0 => CgbSupport::CgbOnly,
+            0x80 => CgbSupport::CgbCompatible,
+            _ => CgbSupport::DmgOnly,
+        };
+        //On CGB cartridges the last title byte is the CGB flag
+        let title_end = if cgb == CgbSupport::DmgOnly { TITLE_END } else { CGB_FLAG };
+        let title = rom[TITLE_START..title_end]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '?' })
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        let type_code = rom[CARTRIDGE_TYPE];
+        let cartridge_type =
+            CartridgeType::from_code(type_code).ok_or(CartridgeError::UnknownCartridgeType(type_code))?;
+
+        let rom_size = match rom[ROM_SIZE] {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            code => return Err(CartridgeError::UnknownRomSize(code)),
+        };
+
+        let ram_size = match rom[RAM_SIZE] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(CartridgeError::UnknownRamSize(code)),
+        };
+
+        let licensee = match rom[OLD_LICENSEE_CODE] {
+            0x33 => Licensee::New([rom[NEW_LICENSEE_CODE], rom[NEW_LICENSEE_CODE + 1]]),
+            code => Licensee::Old(code),
+        };
+
+        Ok(CartridgeHeader {
+            title,
+            cgb,
+            sgb: rom[SGB_FLAG] == 0x03,
+            cartridge_type,
+            rom_size,
+            ram_size,
+            licensee,
+            version: rom[VERSION],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: (rom[GLOBAL_CHECKSUM] as u16) << 8 | rom[GLOBAL_CHECKSUM + 1] as u16,
+        })
+    }
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t = &self.cartridge_type;
+        write!(f, "{} (v{}) - type 0x{:02X} {:?}", self.title, self.version, t.code, t.mbc)?;
+        for (present, name) in [(t.ram, "RAM"), (t.battery, "BATTERY"), (t.timer, "TIMER"), (t.rumble, "RUMBLE")] {
+            if present {
+                write!(f, "+{}", name)?;
+            }
+        }
+        write!(f, ", {} KB ROM, {} KB RAM, {:?}", self.rom_size / 1024, self.ram_size / 1024, self.cgb)?;
+        if self.sgb {
+            write!(f, ", SGB")?;
+        }
+        match self.licensee {
+            Licensee::Old(code) => write!(f, ", licensee 0x{:02X}", code),
+            Licensee::New(code) => write!(f, ", licensee {}{}", code[0] as char, code[1] as char),
+        }
+    }
+}
+
+//Checksum verified by the boot ROM over 0x0134-0x014C
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_START..=VERSION]
+        .iter()
+        .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1))
+}
+
+//Sum of every byte of the ROM except the two checksum bytes
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|&(i, _)| i != GLOBAL_CHECKSUM && i != GLOBAL_CHECKSUM + 1)
+        .fold(0u16, |sum, (_, &b)| sum.wrapping_add(b as u16))
+}
+
+pub struct Cartridge {
+    pub header: CartridgeHeader,
+    pub rom: Vec<u8>,
+}
+
+impl Cartridge {
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        let header = CartridgeHeader::parse(&rom)?;
+
+        let computed = header_checksum(&rom);
+        if computed != header.header_checksum {
+            return Err(CartridgeError::HeaderChecksum { expected: header.header_checksum, computed });
+        }
+
+        //Neither is checked by the hardware, homebrew and patched ROMs often get them wrong
+        if rom.len() != header.rom_size {
+            warn!(target: "bus", "header declares {} bytes of ROM but the file holds {} bytes", header.rom_size, rom.len());
+        }
+        let computed = global_checksum(&rom);
+        if computed != header.global_checksum {
+            warn!(target: "bus", "global checksum mismatch (header: 0x{:04X}, computed: 0x{:04X})", header.global_checksum, computed);
+        }
+
+        Ok(Cartridge { header, rom })
+    }
+
+    //32 KB ROM-only cartridge filled with zeros, with a valid header
+    pub fn blank() -> Cartridge {
+        let mut rom = vec![0; 0x8000];
+        rom[HEADER_CHECKSUM] = header_checksum(&rom);
+        let checksum = global_checksum(&rom);
+        rom[GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2].copy_from_slice(&checksum.to_be_bytes());
+        Cartridge::from_bytes(rom).expect("blank cartridge header is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //32 KB ROM with the given header fields and a valid header checksum
+    fn rom_with(title: &[u8], cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[TITLE_START..TITLE_START + title.len()].copy_from_slice(title);
+        rom[CARTRIDGE_TYPE] = cartridge_type;
+        rom[ROM_SIZE] = rom_size;
+        rom[RAM_SIZE] = ram_size;
+        rom[HEADER_CHECKSUM] = header_checksum(&rom);
+        rom
+    }
+
+    #[test]
+    fn parses_the_header_fields() {
+        let header = CartridgeHeader::parse(&rom_with(b"POKEMON RED", 0x13, 0x05, 0x03)).unwrap();
+        assert_eq!(header.title, "POKEMON RED");
+        assert_eq!(header.cgb, CgbSupport::DmgOnly);
+        assert_eq!(header.cartridge_type.mbc, MbcKind::Mbc3);
+        assert!(header.cartridge_type.ram && header.cartridge_type.battery && !header.cartridge_type.timer);
+        assert_eq!(header.rom_size, 1024 * 1024);
+        assert_eq!(header.ram_size, 32 * 1024);
+    }
+
+    #[test]
+    fn cgb_flag_shortens_the_title() {
+        let mut rom = rom_with(b"ZELDA DX", 0x1B, 0x00, 0x00);
+        rom[CGB_FLAG] = 0x80;
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.cgb, CgbSupport::CgbCompatible);
+        assert_eq!(header.title, "ZELDA DX");
+    }
+
+    #[test]
+    fn rejects_unknown_codes() {
+        assert!(matches!(
+            CartridgeHeader::parse(&rom_with(b"", 0x04, 0x00, 0x00)),
+            Err(CartridgeError::UnknownCartridgeType(0x04))
+        ));
+        assert!(matches!(
+            CartridgeHeader::parse(&rom_with(b"", 0x00, 0x09, 0x00)),
+            Err(CartridgeError::UnknownRomSize(0x09))
+        ));
+        assert!(matches!(
+            CartridgeHeader::parse(&rom_with(b"", 0x00, 0x00, 0x06)),
+            Err(CartridgeError::UnknownRamSize(0x06))
+        ));
+    }
+
+    #[test]
+    fn header_checksum_is_verified() {
+        let mut rom = rom_with(b"TETRIS", 0x00, 0x00, 0x00);
+        rom[VERSION] = 1;
+        assert!(matches!(Cartridge::from_bytes(rom), Err(CartridgeError::HeaderChecksum { .. })));
+    }
+
+    #[test]
+    fn global_checksum_and_size_only_warn() {
+        //Wrong global checksum, and 32 KB declared for a 64 KB file
+        let mut rom = rom_with(b"HOMEBREW", 0x00, 0x00, 0x00);
+        rom.resize(0x10000, 0);
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        assert_eq!(cartridge.rom.len(), 0x10000);
+    }
+
+    #[test]
+    fn too_short_file_is_an_error() {
+        assert!(matches!(Cartridge::from_bytes(vec![0; 0x100]), Err(CartridgeError::TooSmall(0x100))));
+    }
+}
@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+use emulator::mbc::RtcClock;
+use crate::gpu::Palette;
+
+pub const USAGE: &str = "Usage: emulator <rom.gb> [options]
+       emulator disasm <rom.gb> [--from <address>] [--count <n>] [--recursive]
+       emulator tracediff <rom.gb> <reference.log> [--context <n>] [--no-ly-stub]
+
+Options:
+  --scale <1|2|4|8|16|32>   Window scale factor (default 2)
+  --palette <name|colors>   grey, green, pocket or four RRGGBB colors separated by commas
+  --boot-rom <file>         Run the 256 byte DMG boot ROM before the cartridge
+  --headless                Run without opening a window, needs --frames or --dump to stop
+  --frames <n>              Stop after n frames
+  --input <file>            Replay joypad input from a script (implies --headless)
+  --dump <frame>:<file>     Write the screen after the frame as .png or .ppm, frames count
+                            from 1 (implies --headless)
+  --trace <file>            Write a gameboy-doctor trace of the executed instructions,
+                            LY reads as 0x90 like in the gameboy-doctor logs
+  --no-ly-stub              Read the real LY while tracing
+  --save-dir <dir>          Directory for battery saves (default: next to the ROM)
+  --rtc <wall|cycles>       MBC3 clock source, cycles makes runs reproducible (default wall)
+  -h, --help                Print this message
+
+Disassembler options:
+  --from <address>          First address, hexadecimal with 0x or $ (default 0x0100)
+  --count <n>               Number of instructions (default 100, all with --recursive)
+  --recursive               Only decode code reachable from the entry point and vectors
+
+Trace diff options:
+  --context <n>             Instructions shown around the divergence (default 10)
+  --no-ly-stub              Read the real LY instead of 0x90 like gameboy-doctor";
+
+pub struct Options {
+    pub rom_path: PathBuf,
+    pub scale: usize,
+    pub palette: Palette,
+    pub boot_rom: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub input: Option<PathBuf>,
+    pub dumps: Vec<(u64, PathBuf)>,
+    pub trace: Option<PathBuf>,
+    pub ly_stub: bool,
+    pub save_dir: Option<PathBuf>,
+    pub rtc: RtcClock,
+}
+
+pub struct DisasmOptions {
+    pub rom_path: PathBuf,
+    pub from: Option<u16>,
+    //Linear output stops after 100 instructions by default, recursive output doesn't
+    pub count: Option<usize>,
+    pub recursive: bool,
+}
+
+pub struct TraceDiffOptions {
+    pub rom_path: PathBuf,
+    pub reference: PathBuf,
+    pub context: usize,
+    pub ly_stub: bool,
+}
+
+pub enum Command {
+    Run(Options),
+    Disasm(DisasmOptions),
+    TraceDiff(TraceDiffOptions),
+    Help,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_some_and(|arg| arg == "disasm") {
+        args.next();
+        return parse_disasm(args);
+    }
+    if args.peek().is_some_and(|arg| arg == "tracediff") {
+        args.next();
+        return parse_tracediff(args);
+    }
+    let mut rom_path = None;
+    let mut scale = 2;
+    let mut palette = Palette::default();
+    let mut boot_rom = None;
+    let mut headless = false;
+    let mut frames = None;
+    let mut input = None;
+    let mut dumps = Vec::new();
+    let mut trace = None;
+    let mut ly_stub = true;
+    let mut save_dir = None;
+    let mut rtc = RtcClock::WallClock;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--scale" => {
+                scale = match value("--scale")?.parse() {
+                    Ok(s @ (1 | 2 | 4 | 8 | 16 | 32)) => s,
+                    _ => return Err("--scale must be one of 1, 2, 4, 8, 16, 32".to_string()),
+                }
+            }
+            "--palette" => {
+                let name = value("--palette")?;
+                palette = Palette::parse(&name).ok_or(format!("unknown palette '{}'", name))?;
+            }
+            "--boot-rom" => boot_rom = Some(PathBuf::from(value("--boot-rom")?)),
+            "--headless" => headless = true,
+            "--frames" => {
+                let n = value("--frames")?;
+                frames = Some(n.parse().map_err(|_| format!("invalid frame count '{}'", n))?);
+            }
+            "--input" => {
+                input = Some(PathBuf::from(value("--input")?));
+                headless = true;
+            }
+            "--dump" => {
+                let dump = value("--dump")?;
+                let (frame, path) = dump
+                    .split_once(':')
+                    .and_then(|(frame, path)| Some((frame.parse().ok().filter(|&f| f > 0)?, PathBuf::from(path))))
+                    .ok_or(format!("invalid dump '{}', expected <frame>:<file> with a frame from 1", dump))?;
+                dumps.push((frame, path));
+                headless = true;
+            }
+            "--trace" => trace = Some(PathBuf::from(value("--trace")?)),
+            "--no-ly-stub" => ly_stub = false,
+            "--save-dir" => save_dir = Some(PathBuf::from(value("--save-dir")?)),
+            "--rtc" => {
+                rtc = match value("--rtc")?.as_str() {
+                    "wall" => RtcClock::WallClock,
+                    "cycles" => RtcClock::Cycles,
+                    _ => return Err("--rtc must be wall or cycles".to_string()),
+                }
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if rom_path.is_none() => rom_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    //A headless run has no window to close, it must know when to stop
+    if headless && frames.is_none() && dumps.is_empty() {
+        return Err("--headless and --input need --frames or --dump to know when to stop".to_string());
+    }
+    if let Some(n) = frames {
+        if let Some((frame, _)) = dumps.iter().find(|&&(f, _)| f > n as u64) {
+            return Err(format!("--dump at frame {} comes after the last frame ({})", frame, n));
+        }
+    }
+
+    Ok(Command::Run(Options {
+        rom_path: rom_path.ok_or("missing ROM path")?,
+        scale,
+        palette,
+        boot_rom,
+        headless,
+        frames,
+        input,
+        dumps,
+        trace,
+        ly_stub,
+        save_dir,
+        rtc,
+    }))
+}
+
+fn parse_disasm(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut rom_path = None;
+    let mut from = None;
+    let mut count = None;
+    let mut recursive = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--from" => {
+                let address = value("--from")?;
+                from = Some(parse_address(&address).ok_or(format!("invalid address '{}'", address))?);
+            }
+            "--count" => {
+                let n = value("--count")?;
+                count = Some(n.parse().map_err(|_| format!("invalid instruction count '{}'", n))?);
+            }
+            "--recursive" => recursive = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if rom_path.is_none() => rom_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    Ok(Command::Disasm(DisasmOptions {
+        rom_path: rom_path.ok_or("missing ROM path")?,
+        from,
+        count,
+        recursive,
+    }))
+}
+
+fn parse_tracediff(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut paths = Vec::new();
+    let mut context = 10;
+    let mut ly_stub = true;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--context" => {
+                let n = value("--context")?;
+                context = n.parse().map_err(|_| format!("invalid instruction count '{}'", n))?;
+            }
+            "--no-ly-stub" => ly_stub = false,
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if paths.len() < 2 => paths.push(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    Ok(Command::TraceDiff(TraceDiffOptions {
+        rom_path: paths.next().ok_or("missing ROM path")?,
+        reference: paths.next().ok_or("missing reference trace")?,
+        context,
+        ly_stub,
+    }))
+}
+
+//0x150, $150 or 150, always hexadecimal
+fn parse_address(text: &str) -> Option<u16> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('$'))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    fn options(args: &str) -> Options {
+        match parse_args(args) {
+            Ok(Command::Run(options)) => options,
+            _ => panic!("'{}' should parse as a run", args),
+        }
+    }
+
+    fn error(args: &str) -> String {
+        match parse_args(args) {
+            Err(e) => e,
+            Ok(_) => panic!("'{}' should be rejected", args),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let options = options("tetris.gb");
+        assert_eq!(options.rom_path, PathBuf::from("tetris.gb"));
+        assert_eq!(options.scale, 2);
+        assert!(!options.headless);
+        assert!(options.ly_stub);
+        assert_eq!(options.rtc, RtcClock::WallClock);
+    }
+
+    #[test]
+    fn scale_must_be_a_power_of_two() {
+        assert_eq!(options("tetris.gb --scale 4").scale, 4);
+        for bad in ["3", "64", "x"] {
+            assert!(error(&format!("tetris.gb --scale {}", bad)).starts_with("--scale must be"));
+        }
+        assert_eq!(error("tetris.gb --scale"), "missing value for --scale");
+    }
+
+    #[test]
+    fn dump_takes_a_frame_and_a_file() {
+        let options = options("tetris.gb --dump 300:a.png --dump 10:b.ppm");
+        assert!(options.headless);
+        assert_eq!(options.dumps, [(300, PathBuf::from("a.png")), (10, PathBuf::from("b.ppm"))]);
+        for bad in ["300", "x:a.png", "-1:a.png", "0:a.png"] {
+            assert!(error(&format!("tetris.gb --dump {}", bad)).starts_with("invalid dump"));
+        }
+    }
+
+    #[test]
+    fn headless_runs_need_a_stop_condition() {
+        for args in ["tetris.gb --headless", "tetris.gb --input keys.txt"] {
+            assert!(error(args).ends_with("need --frames or --dump to know when to stop"));
+        }
+        assert_eq!(options("tetris.gb --headless --frames 10").frames, Some(10));
+        assert!(options("tetris.gb --input keys.txt --dump 5:a.png").headless);
+        assert_eq!(error("tetris.gb --frames 10 --dump 20:a.png"), "--dump at frame 20 comes after the last frame (10)");
+        //The window is closed by hand
+        assert_eq!(options("tetris.gb").frames, None);
+    }
+
+    #[test]
+    fn rtc_clock() {
+        assert_eq!(options("tetris.gb --rtc cycles").rtc, RtcClock::Cycles);
+        assert_eq!(options("tetris.gb --rtc wall").rtc, RtcClock::WallClock);
+        assert_eq!(error("tetris.gb --rtc host"), "--rtc must be wall or cycles");
+    }
+
+    #[test]
+    fn rom_path_is_required() {
+        assert_eq!(error("--scale 4"), "missing ROM path");
+        assert_eq!(error("disasm --count 10"), "missing ROM path");
+        assert_eq!(error("tracediff"), "missing ROM path");
+        assert_eq!(error("tracediff tetris.gb"), "missing reference trace");
+        assert_eq!(error("tetris.gb other.gb"), "unexpected argument 'other.gb'");
+    }
+
+    #[test]
+    fn addresses_are_hexadecimal_with_or_without_prefix() {
+        for text in ["0x150", "0X150", "$150", "150"] {
+            assert_eq!(parse_address(text), Some(0x150));
+        }
+        assert_eq!(parse_address("0x10000"), None);
+        assert_eq!(parse_address("$"), None);
+        match parse_args("disasm tetris.gb --from $C000") {
+            Ok(Command::Disasm(options)) => assert_eq!(options.from, Some(0xC000)),
+            _ => panic!("disasm should parse"),
+        }
+        assert_eq!(error("disasm tetris.gb --from 0xG0"), "invalid address '0xG0'");
+    }
+
+    #[test]
+    fn disasm_count_is_only_set_when_given() {
+        match parse_args("disasm tetris.gb --recursive") {
+            Ok(Command::Disasm(options)) => assert!(options.recursive && options.count.is_none()),
+            _ => panic!("disasm should parse"),
+        }
+        match parse_args("disasm tetris.gb --recursive --count 20") {
+            Ok(Command::Disasm(options)) => assert_eq!(options.count, Some(20)),
+            _ => panic!("disasm should parse"),
+        }
+        assert_eq!(error("disasm tetris.gb --count x"), "invalid instruction count 'x'");
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert_eq!(error("tetris.gb --fast"), "unknown option '--fast'");
+        assert!(matches!(parse_args("tetris.gb --help"), Ok(Command::Help)));
+    }
+}
@@ -1,5 +1,16 @@
+use log::{debug, trace};
+use crate::asm;
+use crate::cartridge::{Cartridge, CartridgeError, CartridgeHeader};
 use crate::instruction::*;
-use crate::launch;
+use crate::interrupt::InterruptController;
+use crate::joypad::Joypad;
+use crate::mbc::{self, MemoryBankController};
+use crate::save;
+use crate::scheduler::{Event, Scheduler};
+use crate::serial::{self, Serial};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
 use crate::ppu;
 use crate::register;
 use crate::timer::*;
@@ -9,26 +20,70 @@ pub struct CPU{
     stack_pointer:u16,
     pub bus:MemoryBus,
     pub is_halted:bool,
-    ei:u8,
-    di:u8,
-    last_pc:u16,
-    pub jpad_interrupt: bool,
+    //HALT with IME=0 and an interrupt pending: the next opcode fetch doesn't increment PC
+    halt_bug: bool,
+    //STOP mode, CPU, timer and LCD are frozen until a selected joypad line goes low
+    pub is_stopped: bool,
+    //Set by EI, IME turns on once the next instruction has run
+    ime_pending: bool,
+    //Set when LD B,B runs, test ROMs use it as a software breakpoint
+    pub breakpoint: bool,
+    //Set by conditional jumps, calls and returns that are taken, for the cycle count
+    branch_taken: bool,
+    //Cycles of the current instruction already ticked by its bus accesses
+    elapsed_cycles: u32,
+    //Executed instructions are written here when tracing is enabled
+    trace: Option<BufWriter<File>>,
 }
+//Memory access recorded on the flat test bus
+#[cfg(any(test, feature = "flat-bus"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+  Read(u16, u8),
+  Write(u16, u8),
+}
+
 pub struct MemoryBus{
-    pub rom: Vec<u8>,
+    pub header: CartridgeHeader,
+    mbc: Box<dyn MemoryBankController>,
+    //Mapped over 0x0000-0x00FF until the boot ROM writes to 0xFF50
+    boot_rom: Option<Vec<u8>>,
+    //Battery backed carts are saved to this file
+    save_path: Option<PathBuf>,
+    ram_dirty: bool,
     wram:[u8;0x2000],
     hram:[u8;0x80],
     pub ppu:ppu::PPU,
     ime:bool,
-    ie:u8,
-    if_reg:u8,
+    pub interrupts: InterruptController,
     pub timer:Timer,
-    pub joypad: u8,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    //Flat 64 KB RAM replacing the whole memory map, for CPU tests
+    #[cfg(any(test, feature = "flat-bus"))]
+    flat: Option<Box<[u8]>>,
+    //Accesses made by the CPU on the flat RAM, one per M-cycle
+    #[cfg(any(test, feature = "flat-bus"))]
+    pub accesses: Vec<Access>,
+    //LY always reads 0x90, as in the gameboy-doctor reference logs
+    pub ly_stub: bool,
+    //Cycles elapsed since power on
+    pub cycles: u64,
+    scheduler: Scheduler,
+    //Cycle of the last DIV reset, the timer ticks are aligned on it
+    div_reset_at: u64,
+    //Next source address while an OAM DMA is running
+    dma: Option<u16>,
+    //Set when the PPU enters VBlank
+    frame_done: bool,
 }
 
 impl MemoryBus {
   fn read_rom(&self, address: u16) -> u8 {
-    self.rom[address as usize]
+    match &self.boot_rom {
+      Some(boot_rom) if (address as usize) < boot_rom.len() => boot_rom[address as usize],
+      _ => self.mbc.read_rom(address),
+    }
   }
   
   fn wram_read(&self,address: u16)->u8{
@@ -48,46 +103,73 @@ impl MemoryBus {
   }
 
   pub fn bus_read(&self,address:u16)->u8{
+    #[cfg(any(test, feature = "flat-bus"))]
+    if let Some(memory) = &self.flat {
+      return memory[address as usize];
+    }
+    //Only HRAM is reachable while OAM DMA runs
+    if self.dma.is_some() && !(0xFF80..=0xFFFE).contains(&address) {
+      return 0xFF;
+    }
+    self.read(address)
+  }
+
+  fn read(&self,address:u16)->u8{
     match address{
       0x0000..=0x7FFF => self.read_rom(address), //ROM
       0x8000..=0x9FFF => self.ppu.vram_read(address), //VRAM
+      0xA000..=0xBFFF => self.mbc.read_ram(address), //External RAM
       0xC000..=0xDFFF=>self.wram_read(address),//WRAM
       0xE000..=0xFDFF=>0,//ECHO RAM
       0xFE00..=0xFE9F=>self.ppu.oam_read(address),//OAM
       0xFEA0..=0xFEFF=>0,//Not usable
-      0xFF00 => self.joypad, //Joypad
-      0xFF01..=0xFF02 => {
-        println!("Serial transfer Link Cable");
-        0
-      },
+      0xFF00 => self.joypad.read(), //Joypad
+      0xFF01..=0xFF02 => self.serial.serial_read(address), //Serial
       0xFF04..=0xFF07 =>self.timer.timer_read(address), //Timer
-      0xFF0F =>self.if_reg, //IF interrupt flags
+      0xFF0F =>self.interrupts.read_if(), //IF interrupt flags
       0xFF40..=0xFF4B => self.lcd_read(address),
-      0xFF4C..=0xFF7F => panic!(),
+      0xFF4C..=0xFF7F => 0xFF, //Unmapped I/O
       0xFF80..=0xFFFE=>self.hram_read(address),//HRAM
-      0xFFFF =>self.ie,//IE interrupt enable
+      0xFFFF =>self.interrupts.enable,//IE interrupt enable
       _ =>0
     }
     
   }
 
-  fn bus_write(&mut self,address:u16,val:u8){
+  pub fn bus_write(&mut self,address:u16,val:u8){
+    #[cfg(any(test, feature = "flat-bus"))]
+    if let Some(memory) = &mut self.flat {
+      memory[address as usize] = val;
+      return;
+    }
+    if self.dma.is_some() && !(0xFF80..=0xFFFE).contains(&address) {
+      return;
+    }
     match address{
-      0x0000..=0x7FFF => (), //ROM
+      0x0000..=0x7FFF => self.mbc.write_rom(address,val), //MBC registers
       0x8000..=0x9FFF => self.ppu.vram_write(address,val), //VRAM
-      0xA000..=0xBFFF =>(), //External RAM
+      0xA000..=0xBFFF => self.ram_dirty |= self.mbc.write_ram(address,val), //External RAM
       0xC000..=0xDFFF=>self.wram_write(address,val),//WRAM
       0xE000..=0xFDFF=>(),//ECHO RAM
       0xFE00..=0xFE9F=>self.ppu.oam_write(address,val),//OAM
       0xFEA0..=0xFEFF=>(),//Not usable
-      0xFF00 => self.joypad = val | 0xf, //Joypad
-      0xFF01..=0xFF02 => (),
-      0xFF04..=0xFF07 =>self.timer.timer_write(address, val), //Timer
-      0xFF0F =>self.if_reg = val, //IF interrupt flags
+      0xFF00 => self.joypad.write(val), //Joypad
+      0xFF01..=0xFF02 => {
+        self.serial.serial_write(address,val);
+        if address == 0xFF02 && self.serial.transferring() {
+          self.scheduler.schedule(self.cycles + serial::CYCLES_PER_BIT, Event::SerialBit);
+        }
+      } //Serial
+      0xFF04..=0xFF07 =>self.timer_write(address, val), //Timer
+      0xFF0F =>self.interrupts.write_if(val), //IF interrupt flags
       0xFF40..=0xFF4B => self.lcd_write(address,val),
+      0xFF50 if val != 0 => {
+        debug!(target: "bus", "boot ROM disabled");
+        self.boot_rom = None;
+      } //Boot ROM disable
       0xFF4C..=0xFF7F => (),
       0xFF80..=0xFFFE=>self.hram_write(address,val),//HRAM
-      0xFFFF =>self.ie = val,//IE interrupt enable
+      0xFFFF =>self.interrupts.enable = val,//IE interrupt enable
       _ =>()
     }
     
@@ -96,9 +178,10 @@ impl MemoryBus {
   pub fn lcd_read(&self,address:u16)->u8{
     match address{  
         0xFF40 => self.ppu.lcdc,
-        0xFF41 => self.ppu.lcds,
+        0xFF41 => self.ppu.stat_read(),
         0xFF42 => self.ppu.scy,
         0xFF43 => self.ppu.scx,
+        0xFF44 if self.ly_stub => 0x90,
         0xFF44 => self.ppu.ly,
         0xFF45 => self.ppu.lyc,
         0xFF46 => 0,
@@ -114,7 +197,7 @@ impl MemoryBus {
   pub fn lcd_write(&mut self,address:u16,val:u8){
       match address{
           0xFF40 => self.ppu.lcdc = val,
-          0xFF41 => self.ppu.lcds = val,
+          0xFF41 => self.ppu.stat_write(val),
           0xFF42 => self.ppu.scy = val,
           0xFF43 => self.ppu.scx = val,
           0xFF44 => (),
@@ -129,13 +212,124 @@ impl MemoryBus {
       }
   }
 
-  fn dma_transfer(&mut self,start:u8){
-    let address = start as u16 * 0x100;
-    for i in 0..0xA0u16{
-        let val = self.bus_read(address+i);
-        self.ppu.oam_write(i, val);
+  //Use a save file for battery backed carts and load it if it exists
+  pub fn attach_battery(&mut self,path:PathBuf)->io::Result<()>{
+    if !self.header.cartridge_type.battery {
+      return Ok(());
+    }
+    let result = save::load(&path, self.mbc.as_mut());
+    self.save_path = Some(path);
+    result
+  }
+
+  //Write the save file, unless nothing changed since the last flush
+  pub fn flush_battery(&mut self,force:bool)->io::Result<()>{
+    if let Some(path) = &self.save_path {
+      if self.ram_dirty || force {
+        save::write(path, self.mbc.as_mut())?;
+        self.ram_dirty = false;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn tick(&mut self,cycles:u32){
+    self.cycles += cycles as u64;
+    self.mbc.tick(cycles);
+    while let Some((due, event)) = self.scheduler.pop(self.cycles) {
+      self.run_event(due, event);
+    }
+  }
+
+  //Device work due at a given cycle, rescheduling the next occurrence
+  fn run_event(&mut self,due:u64,event:Event){
+    match event {
+      Event::PpuLine => {
+        self.ppu.ppu_step(&mut self.interrupts);
+        match self.ppu.mode() {
+          ppu::MODE_OAM_SCAN => self.scheduler.schedule(due + ppu::OAM_SCAN_CYCLES, Event::PpuMode),
+          ppu::MODE_VBLANK if self.ppu.ly == 144 => self.frame_done = true,
+          _ => (),
+        }
+        self.scheduler.schedule(due + ppu::CYCLES_PER_LINE, Event::PpuLine);
+      }
+      Event::PpuMode => {
+        //The LCD may have been turned off since the line started
+        match self.ppu.mode() {
+          ppu::MODE_OAM_SCAN => {
+            self.ppu.set_mode(ppu::MODE_DRAWING, &mut self.interrupts);
+            self.scheduler.schedule(due + ppu::DRAWING_CYCLES, Event::PpuMode);
+          }
+          ppu::MODE_DRAWING => self.ppu.set_mode(ppu::MODE_HBLANK, &mut self.interrupts),
+          _ => (),
+        }
+      }
+      Event::DivTick => {
+        self.timer.div_tick();
+        self.scheduler.schedule(due + DIV_PERIOD, Event::DivTick);
+      }
+      Event::TimaTick => {
+        self.timer.tima_tick(&mut self.interrupts);
+        if let Some(period) = self.timer.tima_period() {
+          self.scheduler.schedule(due + period, Event::TimaTick);
+        }
+      }
+      Event::SerialBit => {
+        if self.serial.shift(&mut self.interrupts) {
+          self.scheduler.schedule(due + serial::CYCLES_PER_BIT, Event::SerialBit);
+        }
+      }
+      Event::OamDma => {
+        if let Some(source) = self.dma {
+          let val = self.read(source);
+          self.ppu.oam_write(source, val);
+          if source & 0xFF == 0x9F {
+            self.dma = None;
+          } else {
+            self.dma = Some(source + 1);
+            self.scheduler.schedule(due + 4, Event::OamDma);
+          }
+        }
+      }
     }
   }
+
+  pub fn timer_write(&mut self,address:u16,val:u8){
+    self.timer.timer_write(address, val);
+    match address {
+      //The divider restarts, TIMA is clocked from it
+      0xFF04 => {
+        self.div_reset_at = self.cycles;
+        self.scheduler.schedule(self.cycles + DIV_PERIOD, Event::DivTick);
+        self.schedule_tima();
+      }
+      0xFF07 => self.schedule_tima(),
+      _ => (),
+    }
+  }
+
+  fn schedule_tima(&mut self){
+    match self.timer.tima_period() {
+      Some(period) => {
+        let elapsed = self.cycles - self.div_reset_at;
+        let next = self.div_reset_at + (elapsed / period + 1) * period;
+        self.scheduler.schedule(next, Event::TimaTick);
+      }
+      None => self.scheduler.cancel(Event::TimaTick),
+    }
+  }
+
+  //One byte per M-cycle, 160 M-cycles in total
+  fn dma_transfer(&mut self,start:u8){
+    debug!(target: "bus", "OAM DMA from {:04X}", start as u16 * 0x100);
+    self.dma = Some(start as u16 * 0x100);
+    self.scheduler.schedule(self.cycles + 4, Event::OamDma);
+  }
+
+  //True once per frame, when VBlank started since the last call
+  pub fn take_frame_done(&mut self)->bool{
+    std::mem::take(&mut self.frame_done)
+  }
 }
 
 pub enum EmulatorError {
@@ -152,7 +346,7 @@ pub enum EmulatorError {
 }
 
 impl CPU {
-  pub fn new() -> CPU{
+  pub fn new(cartridge: Cartridge, clock: mbc::RtcClock) -> Result<CPU, CartridgeError>{
     let flags = register::FlagsRegister  {
         zero: true,
         subtract: false,
@@ -169,131 +363,259 @@ impl CPU {
       h:0x01,
       l:0x4D,
     };
-    let mem_bus = MemoryBus {
-      rom:launch::launch("./tetris.gb", 64),
+    let mut mem_bus = MemoryBus {
+      header: cartridge.header.clone(),
+      mbc: mbc::from_cartridge(cartridge, clock)?,
+      boot_rom: None,
+      save_path: None,
+      ram_dirty: false,
       wram:[0u8;0x2000],
       hram:[0u8;0x80],
       ppu:ppu::PPU::new(),
       ime: false,
-      ie: 0,
-      if_reg: 0,
+      interrupts: InterruptController::new(),
       timer: Timer::new(),
-      joypad: 0b00111111,
+      joypad: Joypad::new(),
+      serial: Serial::new(),
+      #[cfg(any(test, feature = "flat-bus"))]
+      flat: None,
+      #[cfg(any(test, feature = "flat-bus"))]
+      accesses: Vec::new(),
+      ly_stub: false,
+      cycles: 0,
+      scheduler: Scheduler::new(),
+      div_reset_at: 0,
+      dma: None,
+      frame_done: false,
     };
-    CPU {
+    mem_bus.scheduler.schedule(ppu::OAM_SCAN_CYCLES, Event::PpuMode);
+    mem_bus.scheduler.schedule(ppu::CYCLES_PER_LINE, Event::PpuLine);
+    mem_bus.scheduler.schedule(DIV_PERIOD, Event::DivTick);
+    Ok(CPU {
       registers: regs,
       program_counter: 0x0100,
       stack_pointer: 0xFFFE,
       is_halted: false,
+      halt_bug: false,
+      is_stopped: false,
       bus: mem_bus,
-      ei:0,
-      di:0,
-      last_pc:0,
-      jpad_interrupt: false,
-      
+      ime_pending: false,
+      breakpoint: false,
+      branch_taken: false,
+      elapsed_cycles: 0,
+      trace: None,
+    })
+  }
+
+  //Start from the boot ROM instead of the post-boot state
+  pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>){
+    self.registers.set_af(0);
+    self.registers.set_bc(0);
+    self.registers.set_de(0);
+    self.registers.set_hl(0);
+    self.program_counter = 0x0000;
+    self.stack_pointer = 0x0000;
+    self.bus.boot_rom = Some(boot_rom);
+  }
+
+  pub fn set_trace(&mut self, file: File){
+    self.trace = Some(BufWriter::new(file));
+  }
+
+  //CPU wired to a flat 64 KB RAM with no devices mapped, for instruction tests
+  #[cfg(any(test, feature = "flat-bus"))]
+  pub fn flat() -> CPU {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).expect("ROM-only cartridges are always supported");
+    cpu.bus.flat = Some(vec![0; 0x10000].into_boxed_slice());
+    cpu
+  }
+
+  //Copy an assembled program into memory and start executing it
+  pub fn load_program(&mut self, program: &asm::Program) {
+    for (offset, &byte) in program.bytes.iter().enumerate() {
+      self.bus.bus_write(program.origin.wrapping_add(offset as u16), byte);
     }
+    self.program_counter = program.origin;
+  }
+
+  pub fn pc(&self) -> u16 {
+    self.program_counter
+  }
+
+  pub fn set_pc(&mut self, pc: u16) {
+    self.program_counter = pc;
   }
 
-  fn read_next_byte(&self) -> u8 {
+  pub fn sp(&self) -> u16 {
+    self.stack_pointer
+  }
+
+  pub fn set_sp(&mut self, sp: u16) {
+    self.stack_pointer = sp;
+  }
+
+  pub fn ime(&self) -> bool {
+    self.bus.ime
+  }
 
-    self.bus.bus_read(self.program_counter.wrapping_add(1))
+  pub fn set_ime(&mut self, ime: bool) {
+    self.bus.ime = ime;
   }
 
-  fn read_next_word(&self) -> u16{
+  fn read_next_byte(&mut self) -> u8 {
+    self.read8(self.program_counter.wrapping_add(1))
+  }
+
+  fn read_next_word(&mut self) -> u16{
     let tmp_pc=self.program_counter.wrapping_add(1);
+    let low = self.read8(tmp_pc) as u16;
+    low | ((self.read8(tmp_pc.wrapping_add(1)) as u16) << 8)
+  }
+
+  //Bus accesses take one M-cycle each, the rest of the machine catches up before the access
+  fn read8(&mut self, address: u16) -> u8 {
+    self.internal_cycle();
+    let val = self.bus.bus_read(address);
+    #[cfg(any(test, feature = "flat-bus"))]
+    if self.bus.flat.is_some() {
+      self.bus.accesses.push(Access::Read(address, val));
+    }
+    val
+  }
+
+  fn write8(&mut self, address: u16, val: u8) {
+    self.internal_cycle();
+    self.bus.bus_write(address, val);
+    #[cfg(any(test, feature = "flat-bus"))]
+    if self.bus.flat.is_some() {
+      self.bus.accesses.push(Access::Write(address, val));
+    }
+  }
+
+  fn internal_cycle(&mut self) {
+    self.bus.tick(4);
+    self.elapsed_cycles += 4;
+  }
 
-    self.bus.bus_read(tmp_pc) as u16 | (((self.bus.bus_read(tmp_pc.wrapping_add(1)) as u16) << 8))
+  //CPU state before the next instruction, in the gameboy-doctor format
+  pub fn trace_line(&self) -> String {
+    let pc = self.program_counter;
+    let r = &self.registers;
+    format!(
+      "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+      r.a, u8::from(r.f), r.b, r.c, r.d, r.e, r.h, r.l, self.stack_pointer, pc,
+      self.bus.bus_read(pc), self.bus.bus_read(pc.wrapping_add(1)),
+      self.bus.bus_read(pc.wrapping_add(2)), self.bus.bus_read(pc.wrapping_add(3))
+    )
   }
 
   pub fn step(&mut self) {
-    let mut instruction_byte = self.bus.bus_read(self.program_counter);
-    
+    if self.is_stopped {
+      if self.bus.joypad.read() & 0x0F == 0x0F {
+        return;
+      }
+      self.is_stopped = false;
+    }
+    //No fetch while halted, the rest of the machine keeps running
+    if self.is_halted {
+      self.bus.tick(4);
+      return;
+    }
+    if self.trace.is_some() {
+      let line = self.trace_line();
+      if let Some(trace) = self.trace.as_mut() {
+        let _ = writeln!(trace, "{}", line);
+      }
+    }
+    self.elapsed_cycles = 0;
+    let mut instruction_byte = self.read8(self.program_counter);
+    if self.halt_bug {
+      //The byte after HALT is read twice, as opcode and again as what follows it
+      self.halt_bug = false;
+      self.program_counter = self.program_counter.wrapping_sub(1);
+    }
+
     let prefixed = instruction_byte == 0xCB;
     if prefixed {
-      instruction_byte = self.bus.bus_read(self.program_counter + 1);
+      instruction_byte = self.read8(self.program_counter.wrapping_add(1));
     }
 
-    let next_pc = if let Some(instruction) = Instruction::from_byte(instruction_byte,prefixed) {
+    let info = OpcodeInfo::get(instruction_byte, prefixed);
+    let enable_ime = self.ime_pending;
+    let next_pc = if let Some(instruction) = info.instruction {
+      trace!(target: "cpu", "{:04X}: {}", self.program_counter, info);
+      self.branch_taken = false;
       self.execute(instruction)
     } else {
       panic!("Unkown instruction found for: {}", instruction_byte);
     };
     self.program_counter = next_pc;
-    print!(" Executing PC = {:#06x}, a: {} b : {} c :{} d : {} e: {} h: {} l: {} lcdc :{:08b}, interrupt: {}, SP: {}",self.program_counter,self.registers.a
-  , self.registers.b,self.registers.c,self.registers.d,self.registers.e,self.registers.h,self.registers.l,self.bus.ppu.lcdc,self.bus.if_reg,self.stack_pointer);
-    
+    //Cycles not spent on bus accesses are internal ones, after the last access
+    let cycles = if self.branch_taken { info.branch_cycles } else { info.cycles } as u32;
+    debug_assert!(self.elapsed_cycles <= cycles, "{} took {} cycles instead of {}", info, self.elapsed_cycles, cycles);
+    self.bus.tick(cycles.saturating_sub(self.elapsed_cycles));
+    //Still pending unless the instruction after EI was DI
+    if enable_ime && self.ime_pending {
+      self.ime_pending = false;
+      self.bus.ime = true;
+    }
   }
 
 
   fn execute(&mut self, instruction: Instruction) ->u16{
-
-    let instruction_name = instruction_name(&instruction);
-    print!("{}", instruction_name);
-    self.update_ime();
-    self.last_pc = self.program_counter;
     match instruction { 
       Instruction::ADD(target) => {
         match target {
           ArithmeticTarget::B => {
             let value = self.registers.b;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::C => {
             let value = self.registers.c;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D => {
             let value = self.registers.d;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::E => {
             let value = self.registers.e;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::H => {
             let value = self.registers.h;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::L => {
             let value = self.registers.l;
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::HL => {
             let address = self.registers.get_hl();
-            let value = self.bus.bus_read(address);
+            let value = self.read8(address);
             self.registers.a =  self.add(value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
             let immediate_value = self.read_next_byte();
             self.registers.a =  self.add(immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           }
           ArithmeticTarget::A => {
             let value = self.registers.a;
             let new_value = self.add(value);
             self.registers.a = new_value;
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::SP => {
             self.stack_pointer = self.add_sp();
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
-            self.program_counter.wrapping_add(1)
+            self.program_counter.wrapping_add(2)
           }
           _ => {panic!()}
         }
@@ -304,28 +626,24 @@ impl CPU {
             let bc = self.registers.get_bc();
             let add = self.addhl(bc);
             self.registers.set_hl(add);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::DE =>{
             let de = self.registers.get_de();
             let add =self.addhl(de);
             self.registers.set_hl(add);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL =>{
             let hl = self.registers.get_hl();
             let add = self.addhl(hl);
             self.registers.set_hl(add);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::SP =>{
             let sp =self.stack_pointer;
             let add = self.addhl(sp);
             self.registers.set_hl(add);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           _=>{panic!("")}
@@ -336,56 +654,47 @@ impl CPU {
           ArithmeticTarget::A => {
             let value = self.registers.a;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::B => {
             let value = self.registers.b;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::C => {
             let value = self.registers.c;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D => {
             let value = self.registers.d;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::E => {
             let value = self.registers.e;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::H => {
             let value = self.registers.h;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::L => {
             let value = self.registers.l;
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::HL => {
             let address = self.registers.get_hl();
-            let value = self.bus.bus_read(address);
+            let value = self.read8(address);
             self.registers.a = self.cp(&value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
             let immediate_value = self.read_next_byte();
             self.registers.a = self.cp(&immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           }
           _=>{panic!()}    
@@ -396,56 +705,47 @@ impl CPU {
           ArithmeticTarget::A => {
             let value = self.registers.a;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::B => {
             let value = self.registers.b;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::C => {
             let value = self.registers.c;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D => {
             let value = self.registers.d;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::E => {
             let value = self.registers.e;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::H => {
             let value = self.registers.h;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::L => {
             let value = self.registers.l;
             self.and(value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::HL => {
             let address = self.registers.get_hl();
-            let value = self.bus.bus_read(address);
+            let value = self.read8(address);
             self.and(value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
             let immediate_value = self.read_next_byte();
             self.and(immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           }
           _=>{self.program_counter}
@@ -456,57 +756,48 @@ impl CPU {
           ArithmeticTarget::A => {
             let a =self.registers.a;
             self.registers.a = self.sbc(a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::B => {
             let b = self.registers.b;
             self.registers.a = self.sbc(b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::C => {
             let c = self.registers.c;
             self.registers.a = self.sbc(c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D => {
             let d = self.registers.d;
             self.registers.a = self.sbc(d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::E => {
             let e = self.registers.e;
             self.registers.a = self.sbc(e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::H => {
             let h = self.registers.h;
             self.registers.a = self.sbc(h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::L => {
             let l = self.registers.l;
             self.registers.a = self.sbc(l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL => {
               // Read the value from memory at the address pointed to by HL
               let address = self.registers.get_hl();
-              let value = self.bus.bus_read(address);
+              let value = self.read8(address);
               self.registers.a = self.sbc(value);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 =>{
             let immediate_value = self.read_next_byte();
             self.registers.a = self.sbc(immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           },
           _=>{panic!()}
@@ -517,57 +808,48 @@ impl CPU {
           ArithmeticTarget::A => {
             let a =self.registers.a;
             self.or(&a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::B => {
             let b = self.registers.b;
             self.or(&b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::C => {
             let c = self.registers.c;
             self.or(&c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D => {
             let d = self.registers.d;
             self.or(&d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::E => {
             let e = self.registers.e;
             self.or(&e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::H => {
             let h = self.registers.h;
             self.or(&h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::L => {
             let l =self.registers.l;
             self.or(&l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL => {
               // Read the value from memory at the address pointed to by HL
               let address = self.registers.get_hl();
-              let value = self.bus.bus_read(address);
+              let value = self.read8(address);
               self.or(&value);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
             let immediate_value = self.read_next_byte();
             self.or(&immediate_value);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           },
           _=>{panic!()}
@@ -578,57 +860,48 @@ impl CPU {
           ArithmeticTarget::A => {
             let a = self.registers.a;
             self.xor(&a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::B => {
             let b = self.registers.b;
             self.xor(&b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::C => {
             let c =self.registers.c;
             self.xor(&c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D => {
             let d = self.registers.d;
             self.xor(&d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::E => {
             let e =self.registers.e;
             self.xor(&e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::H => {
             let h = self.registers.h;
             self.xor(&h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::L => {
             let l =self.registers.l;
             self.xor(&l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL => {
             // Read the value from memory at the address pointed to by HL
             let address = self.registers.get_hl();
-            let value = self.bus.bus_read(address);
+            let value = self.read8(address);
             self.xor(&value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
-            let mut immediate_value = self.read_next_byte();
-            self.xor(&mut immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+            let immediate_value = self.read_next_byte();
+            self.xor(&immediate_value);
             self.program_counter.wrapping_add(2)
           },
           _=>{self.program_counter.wrapping_add(1)}
@@ -639,57 +912,48 @@ impl CPU {
           ArithmeticTarget::A => {
             let a = self.registers.a;
             self.cp(&a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::B => {
             let b = self.registers.b;
             self.cp(&b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::C => {
             let c = self.registers.c;
             self.cp(&c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D => {
             let d = self.registers.d;
             self.cp(&d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::E => {
             let e = self.registers.e;
             self.cp(&e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::H => {
             let h = self.registers.h;
             self.cp(&h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::L => {
             let l = self.registers.l;
             self.cp(&l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL => {
             // Read the value from memory at the address pointed to by HL
             let address = self.registers.get_hl();
-            let value = self.bus.bus_read(address);
+            let value = self.read8(address);
             self.cp(&value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           ArithmeticTarget::D8 => {
             let immediate_value = self.read_next_byte();
             self.cp(&immediate_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           },
           _=>{panic!()}
@@ -700,75 +964,63 @@ impl CPU {
           IncDecTarget::A => {
             let a  = self.registers.a;
             self.registers.a = self.inc(a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::B => {
             let b  = self.registers.b; 
             self.registers.b = self.inc(b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::C => {
             let c  = self.registers.c; 
             self.registers.c = self.inc(c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::D => {
             let d  = self.registers.d; 
             self.registers.d = self.inc(d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::E => {
             let e  = self.registers.e; 
             self.registers.e = self.inc(e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::H => {
             let h  = self.registers.h; 
             self.registers.h = self.inc(h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::L => {
             let l  = self.registers.l;           
             self.registers.l = self.inc(l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::HLP => {
             // Read the value from memory at the address pointed to by HL
             let address = self.registers.get_hl();
-            let mut value = self.bus.bus_read(address);
+            let mut value = self.read8(address);
             value = self.inc(value);
             // Write the modified value back to memory
-            self.bus.bus_write(address, value);
-            self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+            self.write8(address, value);
             self.program_counter.wrapping_add(1)
           }
           IncDecTarget::HL => {
             self.registers.set_hl(self.registers.get_hl().wrapping_add(1));
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::BC =>{
             let new_value = self.registers.get_bc().wrapping_add(1);
             self.registers.set_bc(new_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           IncDecTarget::DE =>{
             let new_value = self.registers.get_de().wrapping_add(1);
             self.registers.set_de(new_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           IncDecTarget::SP =>{
             self.stack_pointer = self.stack_pointer.wrapping_add(1);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
         }
@@ -778,155 +1030,129 @@ impl CPU {
           IncDecTarget::A => {
             let a  = self.registers.a; 
             self.registers.a = self.dec(a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::B => {
             let b  = self.registers.b;  
             self.registers.b = self.dec(b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::C => {
             let c  = self.registers.c; 
             self.registers.c = self.dec(c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::D => {
             let d  = self.registers.d; 
             self.registers.d = self.dec(d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::E => {
             let e  = self.registers.e; 
             self.registers.e = self.dec(e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::H => {
             let h  = self.registers.h; 
             self.registers.h = self.dec(h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::L => {
             let l  = self.registers.l; 
             self.registers.l = self.dec(l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::HLP => {
             // Read the value from memory at the address pointed to by HL
             let address = self.registers.get_hl();
-            let mut value = self.bus.bus_read(address);
+            let mut value = self.read8(address);
             value = self.dec(value);
             // Write the modified value back to memory
-            self.bus.bus_write(address,value);
-            self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+            self.write8(address,value);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::HL => {
             self.registers.set_hl(self.registers.get_hl().wrapping_sub(1));
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           IncDecTarget::BC =>{
             let new_value =  self.registers.get_bc().wrapping_sub(1);
             self.registers.set_bc(new_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           IncDecTarget::DE =>{
             let new_value = self.registers.get_de().wrapping_sub(1);
             self.registers.set_de(new_value);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
           IncDecTarget::SP =>{
             self.stack_pointer = self.stack_pointer.wrapping_sub(1);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           }
         }
       }, 
       Instruction::CCF() => {
         self.ccf();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::SCF() => {
         self.scf();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::RRA() => {
         self.rra();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::RLA() => {
         self.rla();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::RRCA() => {
         self.rrca();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::RLCA() => {
         self.rlca();
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       }, 
       Instruction::ADC(target) => {
         match target {
           ArithmeticTarget::A => {
             self.adc(self.registers.a);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::B => {
             self.adc(self.registers.b);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::C => {
             self.adc(self.registers.c);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D => {
             self.adc(self.registers.d);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::E => {
             self.adc(self.registers.e);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::H => {
             self.adc(self.registers.h);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::L => {
             self.adc(self.registers.l);
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::HL => {
-            self.adc(self.bus.bus_read(self.registers.get_hl()));
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
-            self.program_counter.wrapping_add(2)
+            let value = self.read8(self.registers.get_hl());
+            self.adc(value);
+            self.program_counter.wrapping_add(1)
           },
           ArithmeticTarget::D8 => {
             let immediate = self.read_next_byte();
             self.adc(immediate);
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
             self.program_counter.wrapping_add(2)
           },
           _ =>panic!()
@@ -939,52 +1165,43 @@ impl CPU {
         // Update flags
         self.registers.f.subtract = true;
         self.registers.f.half_carry = true;
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       },
       Instruction::BIT(bit, target) => {
         match target {
             PrefixTarget::A => {
               self.bit(bit, self.registers.a);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::B => {
               self.bit(bit, self.registers.b);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::C => {
               self.bit(bit, self.registers.c);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::D => {
               self.bit(bit, self.registers.d);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::E => {
               self.bit(bit, self.registers.e);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::H => {
               self.bit(bit, self.registers.h);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::L => {
               self.bit(bit, self.registers.l);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::HL => {
               // Read the value from memory at the address pointed to by HL
               let address = self.registers.get_hl();
-              let value = self.bus.bus_read(address);
+              let value = self.read8(address);
               self.bit(bit, value);
-              self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
         }
@@ -994,53 +1211,45 @@ impl CPU {
             PrefixTarget::A => {
               let a = self.registers.a;
               self.registers.a = self.res(bit,a);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::B => {
               let b = self.registers.b;
               self.registers.b = self.res(bit,b);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::C => {
               let c = self.registers.c;
               self.registers.c = self.res(bit,c);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::D => {
               let d = self.registers.d;
               self.registers.d = self.res(bit,d);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::E => {
               let e = self.registers.e;
               self.registers.e = self.res(bit,e);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::H => {
               let h = self.registers.h;
               self.registers.h = self.res(bit,h);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::L => {
               let l = self.registers.l;
               self.registers.l = self.res(bit,l);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::HL => {
               // Read the value from memory at the address pointed to by HL
               let address = self.registers.get_hl();
-              let value = self.bus.bus_read(address);
+              let value = self.read8(address);
               let res_val = self.res(bit, value);
               // Write the modified value back to memory
-              self.bus.bus_write(address, res_val);
-              self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+              self.write8(address, res_val);
               self.program_counter.wrapping_add(2)
             }
         }
@@ -1050,53 +1259,45 @@ impl CPU {
             PrefixTarget::A => {
               let a = self.registers.a;
               self.registers.a = self.set(bit,a);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::B => {
               let b = self.registers.b;
               self.registers.b = self.set(bit,b);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::C => {
               let c = self.registers.c;
               self.registers.c = self.set(bit,c);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::D => {
               let d = self.registers.d;
               self.registers.d = self.set(bit,d);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::E => {
               let e = self.registers.e;
               self.registers.e = self.set(bit,e);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::H => {
               let h = self.registers.h;
               self.registers.h = self.set(bit,h);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::L => {
               let l = self.registers.l;
               self.registers.l = self.set(bit,l);
-              self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
               self.program_counter.wrapping_add(2)
             },
             PrefixTarget::HL => {
               // Read the value from memory at the address pointed to by HL
               let address = self.registers.get_hl();
-              let value = self.bus.bus_read(address);
+              let value = self.read8(address);
               let set_val = self.set(bit, value);
               // Write the modified value back to memory
-              self.bus.bus_write(address, set_val);
-              self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+              self.write8(address, set_val);
               self.program_counter.wrapping_add(2)
             }
         }
@@ -1107,60 +1308,52 @@ impl CPU {
                 let a  = self.registers.a;
                 self.srl(&a);
                 self.registers.a >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b  = self.registers.b;
                 self.srl(&b);
                 self.registers.b >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c  = self.registers.c;
                 self.srl(&c);
                 self.registers.c >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d  = self.registers.d;
                 self.srl(&d);
                 self.registers.d >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e  = self.registers.e;
                 self.srl(&e);
                 self.registers.e >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h = self.registers.h;
                 self.srl(&h);
                 self.registers.h >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l  = self.registers.l;
                 self.srl(&l);
                 self.registers.l >>= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let mut value = self.bus.bus_read(address);
+                let mut value = self.read8(address);
                 self.srl(&value);
                 value >>= 1;
                 // Write the modified value back to memory
-                self.bus.bus_write(address, value);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                self.write8(address, value);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1170,53 +1363,45 @@ impl CPU {
               PrefixTarget::A => {
                 let a  = self.registers.a;
                 self.registers.a =self.rr(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b  = self.registers.b;
                 self.registers.b =self.rr(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c  = self.registers.c;
                 self.registers.c =self.rr(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d  = self.registers.d;
                 self.registers.d =self.rr(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e = self.registers.e;
                 self.registers.e =self.rr(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h  = self.registers.h;
                 self.registers.h =self.rr(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l  = self.registers.l;
                 self.registers.l =self.rr(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let rr_val = self.rr(value);
                 // Write the modified value back to memory
-                self.bus.bus_write(address, rr_val);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                self.write8(address, rr_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1226,53 +1411,45 @@ impl CPU {
               PrefixTarget::A => {
                 let a = self.registers.a;
                 self.registers.a = self.rl(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b = self.registers.b;
                 self.registers.b = self.rl(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c = self.registers.c;
                 self.registers.c = self.rl(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d = self.registers.d;
                 self.registers.d = self.rl(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e = self.registers.e;
                 self.registers.e = self.rl(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h = self.registers.h;
                 self.registers.h = self.rl(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l = self.registers.l;
                 self.registers.l = self.rl(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let rl_val = self.rl(value);
                 // Write the modified value back to memory
-                self.bus.bus_write(address, rl_val);
-                self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                self.write8(address, rl_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1282,53 +1459,45 @@ impl CPU {
               PrefixTarget::A => {
                 let a =self.registers.a;
                 self.registers.a = self.rrc(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b =self.registers.b;
                 self.registers.b = self.rrc(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c =self.registers.c;
                 self.registers.c = self.rrc(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d =self.registers.d;
                 self.registers.d = self.rrc(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e =self.registers.e;
                 self.registers.e = self.rrc(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h =self.registers.h;
                 self.registers.h = self.rrc(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l =self.registers.l;
                 self.registers.l = self.rrc(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let rrc_val = self.rrc(value);
                 // Write the modified value back to memory
-                self.bus.bus_write(address, rrc_val);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                self.write8(address, rrc_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1338,54 +1507,46 @@ impl CPU {
               PrefixTarget::A => {
                 let a =self.registers.a;
                 self.registers.a = self.rlc(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b =self.registers.b;
                 self.registers.b = self.rlc(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c =self.registers.c;
                 self.registers.c = self.rlc(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d =self.registers.d;
                 self.registers.d = self.rlc(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e =self.registers.e;
                 self.registers.e = self.rlc(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h =self.registers.h;
                 self.registers.h = self.rlc(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l =self.registers.l;
                 self.registers.l = self.rlc(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let rlc_val = self.rlc(value);
                 
                 // Write the modified value back to memory
-                self.bus.bus_write(address, rlc_val);
-                self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                self.write8(address, rlc_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1395,53 +1556,45 @@ impl CPU {
               PrefixTarget::A => {
                 let a = self.registers.a;
                 self.registers.a =self.sra(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b = self.registers.b;
                 self.registers.b =self.sra(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c = self.registers.c;
                 self.registers.c =self.sra(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d = self.registers.d;
                 self.registers.d =self.sra(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e = self.registers.e;
                 self.registers.e =self.sra(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h = self.registers.h;
                 self.registers.h =self.sra(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l = self.registers.l;
                 self.registers.l =self.sra(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let sra_val = self.sra(value);
                 // Write the modified value back to memory
-                self.bus.bus_write(address, sra_val);
-                self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                self.write8(address, sra_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1452,60 +1605,52 @@ impl CPU {
                 let a = self.registers.a;
                 self.sla(&a);
                 self.registers.a <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b = self.registers.b;
                 self.sla(&b);
                 self.registers.b <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c = self.registers.c;
                 self.sla(&c);
                 self.registers.c <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d = self.registers.d;
                 self.sla(&d);
                 self.registers.d <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e = self.registers.e;
                 self.sla(&e);
                 self.registers.e <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h = self.registers.h;
                 self.sla(&h);
                 self.registers.h <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l = self.registers.l;
                 self.sla(&l);
                 self.registers.l <<= 1;
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let mut value = self.bus.bus_read(address);
+                let mut value = self.read8(address);
                 self.sla(&value);
                 value <<=1;
                 // Write the modified value back to memory
-                self.bus.bus_write(address, value );
-                self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                self.write8(address, value );
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1515,53 +1660,45 @@ impl CPU {
               PrefixTarget::A => {
                 let a = self.registers.a;
                 self.registers.a = self.swap(a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::B => {
                 let b = self.registers.b;
                 self.registers.b = self.swap(b);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::C => {
                 let c = self.registers.c;
                 self.registers.c = self.swap(c);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::D => {
                 let d = self.registers.d;
                 self.registers.d = self.swap(d);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::E => {
                 let e = self.registers.e;
                 self.registers.e = self.swap(e);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::H => {
                 let h = self.registers.h;
                 self.registers.h = self.swap(h);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::L => {
                 let l = self.registers.l;
                 self.registers.l = self.swap(l);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(2)
               },
               PrefixTarget::HL => {
                 // Read the value from memory at the address pointed to by HL
                 let address = self.registers.get_hl();
-                let value = self.bus.bus_read(address);
+                let value = self.read8(address);
                 let swap_val =self.swap(value);
                 // Write the modified value back to memory
-                self.bus.bus_write(address, swap_val);
-                self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                self.write8(address, swap_val);
                 self.program_counter.wrapping_add(2)
               }
           }
@@ -1576,33 +1713,29 @@ impl CPU {
               JumpTest::Carry => self.registers.f.carry,
               JumpTest::Always => true
             };
-            if jump_condition {
-              self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
-            }else {
-              self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
-            }
             self.jump(jump_condition)
           },
           JumpTarget::HL =>{
-            self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
             self.registers.get_hl()
           },
         }  
       }
       Instruction::LD(load_type) => {
+        if matches!(load_type, LoadType::Byte(LoadByteTarget::B, LoadByteSource::B)) {
+          self.breakpoint = true;
+        }
         match load_type {
           LoadType::Byte(target, source) => {
             match target {
               LoadByteTarget::BC =>{
                 match source{
                   LoadByteSource::A=>{
-                    self.bus.bus_write(self.registers.get_bc(), self.registers.a);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_bc(), self.registers.a);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D16=>{
-                    self.registers.set_bc(self.read_next_word());
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+                    let value = self.read_next_word();
+                    self.registers.set_bc(value);
                     self.program_counter.wrapping_add(3)
                   },
                   _=>{panic!{"Err:"}}
@@ -1611,13 +1744,12 @@ impl CPU {
               LoadByteTarget::DE =>{
                 match source{
                   LoadByteSource::A=>{
-                    self.bus.bus_write(self.registers.get_de(), self.registers.a);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_de(), self.registers.a);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D16=>{
-                    self.registers.set_de(self.read_next_word());
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+                    let value = self.read_next_word();
+                    self.registers.set_de(value);
                     self.program_counter.wrapping_add(3)
                   },
                   _=>{panic!{"Err:"}}
@@ -1626,44 +1758,36 @@ impl CPU {
               LoadByteTarget::HL =>{
                 match source{
                   LoadByteSource::A=>{
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.a);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.a);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::B => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.b);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.b);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.c);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.c);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.d);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.d);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.e);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.e);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.h);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.h);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L => {
-                    self.bus.bus_write(self.registers.get_hl(), self.registers.l);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.write8(self.registers.get_hl(), self.registers.l);
                     self.program_counter.wrapping_add(1)
                   },                 
                   LoadByteSource::D16=>{
                     let next_word = self.read_next_word();
                     self.registers.set_hl(next_word);
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(3)
                   },
                   LoadByteSource::SP=>{
@@ -1675,12 +1799,11 @@ impl CPU {
                     self.registers.f.subtract = false;
                     self.registers.f.half_carry = (sp ^ n ^ add) & 0x10 != 0;
                     self.registers.f.carry = (sp ^ n ^ add) & 0x100 != 0;
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   }
                   LoadByteSource::D8 => {
-                    self.bus.bus_write(self.registers.get_hl(), self.read_next_byte());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    let value = self.read_next_byte();
+                    self.write8(self.registers.get_hl(), value);
                     self.program_counter.wrapping_add(2)
                   },
                   _=>{panic!{"Err:"}}
@@ -1690,110 +1813,92 @@ impl CPU {
                 match source{
                   LoadByteSource::D16=>{
                     self.stack_pointer = self.read_next_word();
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(3)
                   },
                   LoadByteSource::HL=>{
                     self.stack_pointer = self.registers.get_hl();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   _=>{panic!()}
                 }
               },
               LoadByteTarget::HLI => {
-                self.bus.bus_write(self.registers.get_hl(), self.registers.a);
+                self.write8(self.registers.get_hl(), self.registers.a);
                 self.registers.set_hl(self.registers.get_hl().wrapping_add(1));
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(1)
               },
               LoadByteTarget::HLD =>{
-                self.bus.bus_write(self.registers.get_hl(), self.registers.a);
+                self.write8(self.registers.get_hl(), self.registers.a);
                 self.registers.set_hl(self.registers.get_hl().wrapping_sub(1));
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                 self.program_counter.wrapping_add(1)
               },
               LoadByteTarget::A => {
                 match source{
                   LoadByteSource::BC =>{
-                    self.registers.a = self.bus.bus_read(self.registers.get_bc());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.a = self.read8(self.registers.get_bc());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::DE =>{
-                    self.registers.a = self.bus.bus_read(self.registers.get_de());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.a = self.read8(self.registers.get_de());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HLI =>{
-                    self.registers.a = self.bus.bus_read(self.registers.get_hl());
+                    self.registers.a = self.read8(self.registers.get_hl());
                     self.registers.set_hl(self.registers.get_hl().wrapping_add(1));
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HLD =>{
-                    self.registers.a = self.bus.bus_read(self.registers.get_hl());
+                    self.registers.a = self.read8(self.registers.get_hl());
                     self.registers.set_hl(self.registers.get_hl().wrapping_sub(1));
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::B =>{
                     self.registers.a = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.a = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.a = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.a = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.a = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.a = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.a = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.a = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.a = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   LoadByteSource::A =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A8 =>{
-                    self.registers.a = self.bus.bus_read(0xFF00 | self.read_next_byte() as u16);
-                    self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+                    let address = 0xFF00 | self.read_next_byte() as u16;
+                    self.registers.a = self.read8(address);
                     self.program_counter.wrapping_add(2)
                   },
                   LoadByteSource::A16 =>{
-                    self.registers.a = self.bus.bus_read(self.read_next_word());
-                    self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                    let address = self.read_next_word();
+                    self.registers.a = self.read8(address);
                     self.program_counter.wrapping_add(3)
                   },
                   LoadByteSource::FF00C =>{
-                    self.registers.a = self.bus.bus_read(0xFF00 | self.registers.c as u16);
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.a = self.read8(0xFF00 | self.registers.c as u16);
                     self.program_counter.wrapping_add(1)
                   },
                   _ => {self.program_counter.wrapping_add(1)}
@@ -1802,47 +1907,38 @@ impl CPU {
               LoadByteTarget::B => {
                 match source{
                   LoadByteSource::B =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.b = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.b = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.b = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.b = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.b = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.b = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.b = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.b = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.b = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {panic!()}
@@ -1852,46 +1948,37 @@ impl CPU {
                 match source{
                   LoadByteSource::B =>{
                     self.registers.c = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.c = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.c = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.c = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.c = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.c = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.c = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.c = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.c = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {self.program_counter.wrapping_add(1)}
@@ -1901,46 +1988,37 @@ impl CPU {
                 match source{
                   LoadByteSource::B =>{
                     self.registers.d = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.d = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.d = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.d = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.d = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.d = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.d = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.d = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.d = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {panic!()}
@@ -1950,46 +2028,37 @@ impl CPU {
                 match source{
                   LoadByteSource::B =>{
                     self.registers.e = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.e = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.e = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.e = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.e = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.e = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.e = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.e = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.e = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {panic!()}
@@ -1999,46 +2068,37 @@ impl CPU {
                 match source{
                   LoadByteSource::B =>{
                     self.registers.h = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.h = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.h = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.h = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
                     self.registers.h = self.registers.l;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.h = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.h = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.h = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.h = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {panic!()}
@@ -2048,46 +2108,37 @@ impl CPU {
                 match source{
                   LoadByteSource::B =>{
                     self.registers.l = self.registers.b;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::C =>{
                     self.registers.l = self.registers.c;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D =>{
                     self.registers.l = self.registers.d;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::E =>{
                     self.registers.l = self.registers.e;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::H =>{
                     self.registers.l = self.registers.h;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::L =>{
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::HL =>{
-                    self.registers.l = self.bus.bus_read(self.registers.get_hl());
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                    self.registers.l = self.read8(self.registers.get_hl());
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::A =>{
                     self.registers.l = self.registers.a;
-                    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(1)
                   },
                   LoadByteSource::D8 =>{
                     self.registers.l = self.read_next_byte();
-                    self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
                     self.program_counter.wrapping_add(2)
                   },
                   _ => {panic!()}
@@ -2096,29 +2147,27 @@ impl CPU {
               LoadByteTarget::A16 =>{
                 match source{
                   LoadByteSource::A =>{
-                    self.bus.bus_write(self.read_next_word(), self.registers.a);
-                    self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+                    let address = self.read_next_word();
+                    self.write8(address, self.registers.a);
                     self.program_counter.wrapping_add(3)
                   },
                   LoadByteSource::SP =>{
                     let sp = self.stack_pointer;
                     let word = self.read_next_word();
-                    self.bus.bus_write(word, sp as u8);
-                    self.bus.bus_write(word.wrapping_add(1), (sp >> 8) as u8);
-                    self.bus.timer.timer_tick(20,self.bus.ppu.lcdc);
+                    self.write8(word, sp as u8);
+                    self.write8(word.wrapping_add(1), (sp >> 8) as u8);
                     self.program_counter.wrapping_add(3)
                   },
                   _=>{panic!()}
                 }
               },
               LoadByteTarget::A8 =>{
-                self.bus.bus_write(0xFF00 | self.read_next_byte() as u16, self.registers.a);
-                self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
+                let address = 0xFF00 | self.read_next_byte() as u16;
+                self.write8(address, self.registers.a);
                 self.program_counter.wrapping_add(2)
               },
               LoadByteTarget::FF00C => {
-                self.bus.bus_write(0xFF00 | self.registers.c as u16, self.registers.a);
-                self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
+                self.write8(0xFF00 | self.registers.c as u16, self.registers.a);
                 self.program_counter.wrapping_add(1)
               },
             }  
@@ -2133,7 +2182,6 @@ impl CPU {
             StackTarget::AF => self.registers.get_af(),
           };
           self.push(value);
-          self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
           self.program_counter.wrapping_add(1)
       }
       Instruction::POP(target) => {
@@ -2144,7 +2192,6 @@ impl CPU {
               StackTarget::HL => self.registers.set_hl(result),
               StackTarget::AF => self.registers.set_af(result),
           };
-          self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
           self.program_counter.wrapping_add(1)
       }
       Instruction::CALL(test) => {
@@ -2155,31 +2202,19 @@ impl CPU {
             JumpTest::Carry => self.registers.f.carry,
             JumpTest::Always => true,
           };
-          if jump_condition {
-            self.bus.timer.timer_tick(24,self.bus.ppu.lcdc);
-          }else{
-            self.bus.timer.timer_tick(12,self.bus.ppu.lcdc);
-          }
           self.call(jump_condition)
       }
       Instruction::RET(test) => {
-        let mut always = false;
           let jump_condition = match test {
             JumpTest::NotZero => !self.registers.f.zero,
             JumpTest::NotCarry => !self.registers.f.carry,
             JumpTest::Zero => self.registers.f.zero,
             JumpTest::Carry => self.registers.f.carry,
-            JumpTest::Always => {
-              always=true;
-              true
-            }
+            JumpTest::Always => true,
           };
-          if always {
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
-          }else if jump_condition {
-            self.bus.timer.timer_tick(20,self.bus.ppu.lcdc);
-          }else {
-            self.bus.timer.timer_tick(8,self.bus.ppu.lcdc); 
+          //RET cc spends an M-cycle checking the condition, before the pops
+          if !matches!(test, JumpTest::Always) {
+            self.internal_cycle();
           }
           self.return_(jump_condition)
       }
@@ -2191,89 +2226,78 @@ impl CPU {
           JumpTest::Carry => self.registers.f.carry,
           JumpTest::Always => true,
         };
-        if jump_condition {
-          self.bus.timer.timer_tick(12,self.bus.ppu.lcdc)
-        }else{
-          self.bus.timer.timer_tick(8,self.bus.ppu.lcdc);
-        }
-        
         self.jr(jump_condition)
       }
       Instruction::STOP() => {
-        self.is_halted = true;
-        //Halt display until button pressed
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
+        self.is_stopped = true;
+        self.bus.timer_write(0xFF04, 0);
         self.program_counter.wrapping_add(2)
       }
       Instruction::NOP() => {
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       } 
       Instruction::HALT() => {
-        self.is_halted = true;
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
-        self.program_counter.wrapping_add(1)
+        if self.bus.ime || !self.interrupt_pending() {
+          self.is_halted = true;
+          self.program_counter.wrapping_add(1)
+        } else if self.ime_pending {
+          //EI; HALT with an interrupt pending: it is serviced and returns to the HALT
+          self.program_counter
+        } else {
+          self.halt_bug = true;
+          self.program_counter.wrapping_add(1)
+        }
       }
       Instruction::RETI() => {
         let ret = self.return_(true);
+        //No delay, unlike EI
         self.bus.ime = true;
-        self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
         ret
       }
       Instruction::EI() => {
-        self.ei += 3;
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
+        self.ime_pending = true;
         self.program_counter.wrapping_add(1)
       }
       Instruction::DI() => {
-        self.di += 3;
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
+        self.bus.ime = false;
+        self.ime_pending = false;
         self.program_counter.wrapping_add(1)
       }
       Instruction::PREFIX() => {
-        self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
         self.program_counter.wrapping_add(1)
       }
       Instruction::RST(restart) => {
         match restart{
           RestartTarget::H00 =>{
-            self.push(self.program_counter);
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+            self.push(self.program_counter.wrapping_add(1));
             0x00
           },
           RestartTarget::H08 => {
-            self.push(self.program_counter);
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+            self.push(self.program_counter.wrapping_add(1));
             0x08
           },
           RestartTarget::H10 => {
-            self.push(self.program_counter);
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
+            self.push(self.program_counter.wrapping_add(1));
             0x10
           },
           RestartTarget::H18 => {
             self.push(self.program_counter.wrapping_add(1));
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
             0x18
           },
           RestartTarget::H20 =>{
             self.push(self.program_counter.wrapping_add(1));
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
             0x20
           },
           RestartTarget::H28 => {
             self.push(self.program_counter.wrapping_add(1));
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
             0x28
           },
           RestartTarget::H30 => {
             self.push(self.program_counter.wrapping_add(1));
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
             0x30
           }
           RestartTarget::H38 => {
             self.push(self.program_counter.wrapping_add(1));
-            self.bus.timer.timer_tick(16,self.bus.ppu.lcdc);
             0x38
           },
         }
@@ -2283,17 +2307,19 @@ impl CPU {
       },
     }
   }
-  fn push(&mut self, val:u16){  
+  //The internal cycle comes before the two writes
+  fn push(&mut self, val:u16){
+    self.internal_cycle();
     self.stack_pointer = self.stack_pointer.wrapping_sub(1);
-    self.bus.bus_write(self.stack_pointer, ((val & 0xFF00) >> 8) as u8);
+    self.write8(self.stack_pointer, ((val & 0xFF00) >> 8) as u8);
     self.stack_pointer = self.stack_pointer.wrapping_sub(1);
-    self.bus.bus_write(self.stack_pointer, (val & 0xFF) as u8);    
+    self.write8(self.stack_pointer, (val & 0xFF) as u8);    
   }
 
   fn pop(&mut self)->u16{
-    let lsb = self.bus.bus_read(self.stack_pointer) as u16;
+    let lsb = self.read8(self.stack_pointer) as u16;
     self.stack_pointer = self.stack_pointer.wrapping_add(1);
-    let msb = self.bus.bus_read(self.stack_pointer) as u16;
+    let msb = self.read8(self.stack_pointer) as u16;
     self.stack_pointer = self.stack_pointer.wrapping_add(1);
     (msb << 8) | lsb
   }
@@ -2323,7 +2349,7 @@ impl CPU {
 
   fn add_sp(&mut self) -> u16{
     let n = self.read_next_byte() as i8;
-    let sp = self.program_counter as i32;
+    let sp = self.stack_pointer as i32;
     let nn = n as i32;
     let result = sp.wrapping_add(nn);
 
@@ -2432,7 +2458,6 @@ impl CPU {
     self.registers.f.carry= adjust & 0x60 != 0;
     self.registers.f.half_carry = false;
 
-    self.bus.timer.timer_tick(4,self.bus.ppu.lcdc);
     self.program_counter.wrapping_add(1)
   }
 
@@ -2573,7 +2598,7 @@ impl CPU {
       let carry = value & 0x01;
 
       // Perform a right rotation through the carry bit on the value
-      let val = (value >> 1) | (value << 7);
+      let val = value.rotate_right(1);
 
       // Update flags
       self.registers.f.zero = val == 0;
@@ -2588,7 +2613,7 @@ impl CPU {
       let carry = value & 0x80;
 
       // Perform a left rotation through the carry bit on the value
-      let val = (value << 1) | (value >> 7);
+      let val = value.rotate_left(1);
 
       // Update flags
       self.registers.f.zero = val == 0;
@@ -2626,7 +2651,7 @@ impl CPU {
 
   fn swap(&mut self, value:u8) -> u8{
       // Perform the swap operation by exchanging the upper and lower nibbles
-      let val = (value << 4) | (value >> 4);
+      let val = value.rotate_left(4);
 
       // Update flags
       self.registers.f.zero = value == 0;
@@ -2638,8 +2663,11 @@ impl CPU {
 
 
   fn jump(&mut self, should_jump: bool) -> u16 {
+    self.branch_taken = should_jump;
+    //The address is read even when the jump isn't taken
+    let address = self.read_next_word();
     if should_jump {
-      self.read_next_word()
+      address
     } else {
       self.program_counter.wrapping_add(3)
     }
@@ -2647,8 +2675,9 @@ impl CPU {
 
 
   fn jr(&mut self, should_jump: bool) -> u16 {
+    self.branch_taken = should_jump;
+    let r8 = self.read_next_byte() as i8;
     if should_jump {
-      let r8 = self.read_next_byte() as i8;
       let new_pc = ((self.program_counter as i16).wrapping_add(r8 as i16)) as u16;
       new_pc.wrapping_add(2)
     } else {
@@ -2657,16 +2686,19 @@ impl CPU {
   }
 
   fn call(&mut self, should_jump: bool) -> u16 {
+    self.branch_taken = should_jump;
     let next_pc = self.program_counter.wrapping_add(3);
+    let address = self.read_next_word();
     if should_jump {
       self.push(next_pc);
-      self.read_next_word()
-      } else {
-        next_pc
-      }
+      address
+    } else {
+      next_pc
+    }
   }
 
   fn return_(&mut self, should_jump: bool) -> u16 {
+    self.branch_taken = should_jump;
     if should_jump {
       self.pop()
     } else {
@@ -2675,524 +2707,495 @@ impl CPU {
   }
   
   pub fn interrupts(&mut self) -> Result<(), EmulatorError> {
-    if self.bus.ppu.vblank_interrupt == 1 {
-        self.bus.if_reg |= 1 << 0;
-
+    //HALT ends on any enabled interrupt, even with IME off
+    if self.is_halted && self.interrupt_pending() {
+      self.is_halted = false;
     }
-    if self.bus.ppu.stat_interrupt == 1 {
-      self.bus.if_reg |= 1 << 1;
+
+    if self.bus.ime && self.interrupt_pending() {
+      self.dispatch_interrupt();
     }
+    Ok(())
+  }
+
+  fn interrupt_pending(&self) -> bool {
+    self.bus.interrupts.pending().is_some()
+  }
 
-    if self.bus.timer.timer_interrupt == 1 {
-      self.bus.if_reg |= 1 << 2;
+  //Five M-cycles: two wait states, the two halves of the PC push, then the jump to the vector
+  fn dispatch_interrupt(&mut self) {
+    self.bus.ime = false;
+    self.bus.tick(8);
+    let pc = self.program_counter;
+    self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    self.bus.bus_write(self.stack_pointer, (pc >> 8) as u8);
+    self.bus.tick(4);
+    //The interrupt is picked after the high byte push, which can overwrite IE and cancel it
+    let pending = self.bus.interrupts.pending();
+    self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    self.bus.bus_write(self.stack_pointer, pc as u8);
+    self.bus.tick(4);
+    if let Some(interrupt) = pending {
+      self.bus.interrupts.acknowledge(interrupt);
+      self.program_counter = interrupt.vector();
+      debug!(target: "cpu", "{:?} interrupt, returning to {:04X}", interrupt, pc);
+    } else {
+      debug!(target: "cpu", "interrupt cancelled");
+      self.program_counter = 0x0000;
     }
-    if self.jpad_interrupt{
-      self.bus.if_reg |= 1 << 4;
+    self.bus.tick(4);
+  }
+}
+
+//string corresponding to the instruction used
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::joypad::Buttons;
+
+  #[test]
+  fn trace_line_matches_gameboy_doctor() {
+    let mut cpu = CPU::flat();
+    cpu.load_program(&asm::assemble("NOP\nJP $0150\nADC A,$00", 0x0100).unwrap());
+    assert_eq!(cpu.trace_line(), "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01");
+    cpu.step();
+    assert_eq!(cpu.trace_line(), "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,CE");
+    cpu.step();
+    assert!(cpu.trace_line().ends_with("SP:FFFE PC:0150 PCMEM:00,00,00,00"));
+  }
+
+  fn run(source: &str) -> CPU {
+    let mut cpu = CPU::flat();
+    cpu.load_program(&asm::assemble(source, 0x0100).unwrap());
+    cpu
+  }
+
+  fn step(cpu: &mut CPU, count: usize) {
+    for _ in 0..count {
+      let _ = cpu.interrupts();
+      cpu.step();
     }
+  }
 
-    if self.bus.ime {
-        let interrupt_flags = self.bus.ie & self.bus.if_reg;
-        if interrupt_flags != 0 {
-            self.bus.ime = false; // Disable further interrupts
-
-            if interrupt_flags & 0b00001 != 0 {
-              self.bus.ppu.vblank_interrupt = 0;
-              self.bus.if_reg &= !(1 << 0);
-              self.handle_interrupt(0x0040)?; // V-Blank interrupt
-            } else if interrupt_flags & 0b00010 != 0 {
-              self.bus.ppu.stat_interrupt = 0;
-              self.bus.if_reg &= !(1 << 1);
-              self.handle_interrupt(0x0048)?; // LCD STAT interrupt
-            } else if interrupt_flags & 0b00100 != 0 {
-              self.bus.timer.timer_interrupt = 0;
-              self.bus.if_reg &= !(1 << 2);
-              self.handle_interrupt(0x0050)?; // Timer interrupt
-            } else if interrupt_flags & 0b01000 != 0 {
-              self.handle_interrupt(0x0058)?; // Serial interrupt
-            } else if interrupt_flags & 0b10000 != 0 {
-              self.jpad_interrupt = false;
-              self.bus.if_reg &= !(1 << 4);
-              self.handle_interrupt(0x0060)?; // Joypad interrupt
-            }
+  #[test]
+  fn straight_line_instructions_advance_pc_by_their_length() {
+    for prefixed in [false, true] {
+      for opcode in 0..=255u8 {
+        let info = OpcodeInfo::get(opcode, prefixed);
+        let control_flow = ["JP", "JR", "CALL", "RET", "RETI", "RST", "HALT", "STOP", "PREFIX"];
+        if info.instruction.is_none() || control_flow.contains(&info.mnemonic) {
+          continue;
+        }
+        let mut cpu = CPU::flat();
+        let code: &[u8] = if prefixed { &[0xCB, opcode] } else { &[opcode] };
+        for (i, &byte) in code.iter().enumerate() {
+          cpu.bus.bus_write(0x0200 + i as u16, byte);
         }
+        cpu.set_pc(0x0200);
+        cpu.step();
+        assert_eq!(cpu.pc(), 0x0200 + info.length as u16, "{}", info);
+      }
     }
-    Ok(())
   }
 
-  fn handle_interrupt(&mut self, addr: u16) -> Result<(), EmulatorError> {
-    //Do nothing during 2 cycles 
-    self.bus.timer.timer_tick(2,self.bus.ppu.lcdc);
+  #[test]
+  fn add_sp_adds_the_signed_offset_to_sp() {
+    let mut cpu = run("LD SP,$FFF8\nADD SP,$02\nADD SP,-4\nLD HL,SP+1");
+    step(&mut cpu, 2);
+    assert_eq!((cpu.sp(), cpu.pc()), (0xFFFA, 0x0105));
+    step(&mut cpu, 1);
+    assert_eq!(cpu.sp(), 0xFFF6);
+    //Flags come from the low byte, 0xFA + 0xFC carries out of both nibbles
+    assert!(cpu.registers.f.carry && cpu.registers.f.half_carry);
+    step(&mut cpu, 1);
+    assert_eq!(cpu.registers.get_hl(), 0xFFF7);
+  }
 
+  #[test]
+  fn rst_pushes_the_next_instruction() {
+    for (vector, opcode) in (0..8).map(|i| (i * 8, 0xC7 + i as u8 * 8)) {
+      let mut cpu = CPU::flat();
+      cpu.bus.bus_write(0x0200, opcode);
+      cpu.set_pc(0x0200);
+      cpu.set_sp(0xD000);
+      cpu.step();
+      assert_eq!(cpu.pc(), vector);
+      assert_eq!((cpu.bus.bus_read(0xCFFF), cpu.bus.bus_read(0xCFFE)), (0x02, 0x01));
+    }
+  }
 
-    self.bus.timer.timer_tick(2,self.bus.ppu.lcdc);
-    // Push the return address onto the stack
-    let pc = self.last_pc;
-    self.push(pc);
-    
-    // Jump to the interrupt handler
-    self.program_counter = addr;
-    Ok(())
+  #[test]
+  fn halt_waits_for_an_interrupt_with_ime_off() {
+    let mut cpu = run("HALT\nINC A");
+    cpu.bus.interrupts.enable = 0x04;
+    step(&mut cpu, 10);
+    assert!(cpu.is_halted);
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0101, 0x01));
+    //Timers keep running while halted
+    assert_eq!(cpu.bus.cycles, 4 + 9 * 4);
+    cpu.bus.interrupts.write_if(0x04);
+    step(&mut cpu, 1);
+    assert!(!cpu.is_halted);
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0102, 0x02));
   }
 
-  
-  fn update_ime(&mut self){
-    //Test if there is a delayed DI instruction
-    if self.di > 1 {
-      self.di -= 1;
-    }
-    //If delay ended, update IME
-    if self.di == 1 {
-      self.bus.ime = false;
-      self.di = 0;
+  #[test]
+  fn halt_wakes_on_timer_overflow() {
+    let mut cpu = run("HALT\nINC A");
+    cpu.bus.interrupts.enable = 0x04;
+    cpu.bus.timer_write(0xFF05, 0xFF);
+    cpu.bus.timer_write(0xFF07, 0x05);
+    step(&mut cpu, 3);
+    assert!(cpu.is_halted);
+    step(&mut cpu, 2);
+    assert_eq!(cpu.registers.a, 0x02);
+  }
+
+  #[test]
+  fn halt_with_ime_on_jumps_to_the_handler() {
+    let mut cpu = run("EI\nNOP\nHALT\nINC A");
+    cpu.bus.interrupts.enable = 0x01;
+    step(&mut cpu, 5);
+    assert!(cpu.is_halted);
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 1);
+    assert!(!cpu.is_halted);
+    //Interrupt dispatched, then the NOP at the VBlank vector
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0041, 0x01));
+    assert_eq!(cpu.bus.interrupts.read_if() & 0x01, 0);
+  }
+
+  #[test]
+  fn stop_waits_for_a_button() {
+    let mut cpu = run("STOP\nINC A");
+    cpu.bus.joypad.write(0x20);
+    step(&mut cpu, 1);
+    let cycles = cpu.bus.cycles;
+    step(&mut cpu, 10);
+    assert!(cpu.is_stopped);
+    //Nothing runs, not even the timer
+    assert_eq!((cpu.pc(), cpu.registers.a, cpu.bus.cycles), (0x0102, 0x01, cycles));
+    //Buttons aren't selected, only the d-pad
+    cpu.bus.joypad.set_buttons(Buttons { a: true, ..Buttons::default() });
+    step(&mut cpu, 1);
+    assert!(cpu.is_stopped);
+    cpu.bus.joypad.set_buttons(Buttons { up: true, ..Buttons::default() });
+    step(&mut cpu, 1);
+    assert!(!cpu.is_stopped);
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0103, 0x02));
+  }
+
+  #[test]
+  fn stop_resets_div() {
+    let mut cpu = run("STOP");
+    cpu.bus.tick(300);
+    assert_ne!(cpu.bus.timer.timer_read(0xFF04), 0);
+    step(&mut cpu, 1);
+    assert_eq!(cpu.bus.timer.timer_read(0xFF04), 0);
+  }
+
+  #[test]
+  fn interrupt_vectors() {
+    for bit in 0..5 {
+      let mut cpu = run("NOP");
+      cpu.set_pc(0x1234);
+      cpu.set_sp(0xD000);
+      cpu.set_ime(true);
+      cpu.bus.interrupts.enable = 0x1F;
+      cpu.bus.interrupts.write_if(1 << bit);
+      let _ = cpu.interrupts();
+      assert_eq!(cpu.pc(), 0x0040 + 8 * bit as u16);
+      assert_eq!(cpu.sp(), 0xCFFE);
+      assert_eq!((cpu.bus.bus_read(0xCFFE), cpu.bus.bus_read(0xCFFF)), (0x34, 0x12));
+      assert_eq!(cpu.bus.interrupts.read_if(), 0xE0);
+      assert!(!cpu.ime());
+      assert_eq!(cpu.bus.cycles, 20);
     }
-    //Test if there is a delayed EI instruction
-    if self.ei > 1 {
-      self.ei -= 1; 
+  }
+
+  #[test]
+  fn interrupt_priority_follows_the_bit_order() {
+    let mut cpu = run("NOP");
+    cpu.set_ime(true);
+    cpu.bus.interrupts.enable = 0x1C;
+    cpu.bus.interrupts.write_if(0x1F);
+    let _ = cpu.interrupts();
+    //VBlank and STAT are requested but not enabled
+    assert_eq!(cpu.pc(), 0x0050);
+    assert_eq!(cpu.bus.interrupts.read_if(), 0xFB);
+  }
+
+  #[test]
+  fn interrupt_returns_to_the_next_instruction() {
+    let mut cpu = CPU::flat();
+    cpu.load_program(&asm::assemble("INC B\nRETI", 0x0040).unwrap());
+    cpu.load_program(&asm::assemble("INC A\nINC A\nINC A\nloop: JR loop", 0x0100).unwrap());
+    cpu.bus.interrupts.enable = 0x01;
+    step(&mut cpu, 1);
+    cpu.set_ime(true);
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 10);
+    assert_eq!((cpu.registers.a, cpu.registers.b), (0x04, 0x01));
+    assert_eq!(cpu.sp(), 0xFFFE);
+  }
+
+  #[test]
+  fn game_writes_to_if_are_authoritative() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    cpu.set_ime(true);
+    cpu.bus.bus_write(0xFFFF, 0x04);
+    cpu.bus.timer_write(0xFF05, 0xFF);
+    cpu.bus.timer_write(0xFF07, 0x05);
+    cpu.bus.tick(16);
+    assert_eq!(cpu.bus.bus_read(0xFF0F), 0xE4);
+    cpu.bus.bus_write(0xFF0F, 0x00);
+    let _ = cpu.interrupts();
+    assert_eq!(cpu.pc(), 0x0100);
+    //Software can request interrupts too
+    cpu.bus.bus_write(0xFF0F, 0x04);
+    let _ = cpu.interrupts();
+    assert_eq!(cpu.pc(), 0x0050);
+  }
+
+  #[test]
+  fn interrupt_cancelled_by_the_stack_overwriting_ie() {
+    //The high byte of PC lands in IE (0xFFFF) before the vector is picked
+    for (pc, vector) in [(0x0200, 0x0000), (0x0100, 0x0040)] {
+      let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+      cpu.set_pc(pc);
+      cpu.set_sp(0x0000);
+      cpu.set_ime(true);
+      cpu.bus.interrupts.enable = 0x01;
+      cpu.bus.interrupts.write_if(0x01);
+      let _ = cpu.interrupts();
+      assert_eq!(cpu.pc(), vector);
+      assert_eq!(cpu.bus.interrupts.enable, (pc >> 8) as u8);
+      assert_eq!(cpu.bus.cycles, 20);
     }
-    //If delay ended, update IME
-    if self.ei == 1 {
-      self.bus.ime = true;
-      self.ei = 0;
+  }
+
+  #[test]
+  fn ei_enables_interrupts_after_the_next_instruction() {
+    let mut cpu = run("EI\nINC A\nINC A");
+    cpu.bus.interrupts.enable = 0x01;
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 1);
+    assert!(!cpu.ime());
+    step(&mut cpu, 1);
+    assert!(cpu.ime());
+    //Dispatched before the second INC A
+    step(&mut cpu, 1);
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0041, 0x02));
+    assert_eq!((cpu.bus.bus_read(0xFFFC), cpu.bus.bus_read(0xFFFD)), (0x02, 0x01));
+  }
+
+  #[test]
+  fn di_right_after_ei_keeps_interrupts_off() {
+    let mut cpu = run("EI\nDI\nINC A\nINC A");
+    cpu.bus.interrupts.enable = 0x01;
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 4);
+    assert!(!cpu.ime());
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0104, 0x03));
+  }
+
+  #[test]
+  fn di_takes_effect_immediately() {
+    let mut cpu = run("DI\nINC A");
+    cpu.set_ime(true);
+    cpu.bus.interrupts.enable = 0x01;
+    step(&mut cpu, 1);
+    assert!(!cpu.ime());
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 1);
+    assert_eq!((cpu.pc(), cpu.registers.a), (0x0102, 0x02));
+  }
+
+  #[test]
+  fn ei_halt_with_a_pending_interrupt_returns_to_the_halt() {
+    let mut cpu = CPU::flat();
+    cpu.load_program(&asm::assemble("INC B\nRETI", 0x0040).unwrap());
+    cpu.load_program(&asm::assemble("EI\nHALT\nINC A", 0x0100).unwrap());
+    cpu.bus.interrupts.enable = 0x01;
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 3);
+    assert!(!cpu.is_halted);
+    assert_eq!(cpu.pc(), 0x0041);
+    assert_eq!((cpu.bus.bus_read(0xFFFC), cpu.bus.bus_read(0xFFFD)), (0x01, 0x01));
+    //RETI, then the HALT again which now waits
+    step(&mut cpu, 3);
+    assert!(cpu.is_halted);
+    assert_eq!((cpu.pc(), cpu.registers.a, cpu.registers.b), (0x0102, 0x01, 0x01));
+  }
+
+  #[test]
+  fn reti_enables_interrupts_immediately() {
+    let mut cpu = CPU::flat();
+    cpu.load_program(&asm::assemble("RETI", 0x0040).unwrap());
+    cpu.load_program(&asm::assemble("INC B\nRETI", 0x0050).unwrap());
+    cpu.load_program(&asm::assemble("INC A\nINC A", 0x0100).unwrap());
+    cpu.set_ime(true);
+    cpu.bus.interrupts.enable = 0x05;
+    cpu.bus.interrupts.write_if(0x05);
+    //VBlank handler returns straight into the timer one, before INC A
+    step(&mut cpu, 2);
+    assert_eq!((cpu.pc(), cpu.registers.a, cpu.registers.b), (0x0051, 0x01, 0x01));
+  }
+
+  #[test]
+  fn serial_transfer_takes_eight_bit_periods() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    cpu.bus.bus_write(0xFF01, b'!');
+    cpu.bus.bus_write(0xFF02, 0x81);
+    cpu.bus.tick(8 * 512 - 4);
+    assert_eq!(cpu.bus.bus_read(0xFF02), 0xFF);
+    cpu.bus.tick(4);
+    assert_eq!(cpu.bus.bus_read(0xFF02), 0x7F);
+    assert_eq!(cpu.bus.bus_read(0xFF0F), 0xE8);
+  }
+
+  #[test]
+  fn oam_dma_copies_one_byte_per_m_cycle() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    for i in 0..0xA0 {
+      cpu.bus.bus_write(0xC000 + i, i as u8 + 1);
     }
+    cpu.bus.bus_write(0xFF80, 0x42);
+    cpu.bus.bus_write(0xFF46, 0xC0);
+    cpu.bus.tick(8);
+    assert_eq!((cpu.bus.ppu.oam[1], cpu.bus.ppu.oam[2]), (0x02, 0x00));
+    //Only HRAM is reachable meanwhile
+    assert_eq!((cpu.bus.bus_read(0xC000), cpu.bus.bus_read(0xFF80)), (0xFF, 0x42));
+    cpu.bus.tick(160 * 4 - 8);
+    assert_eq!((cpu.bus.ppu.oam[0x9F], cpu.bus.bus_read(0xC000)), (0xA0, 0x01));
   }
-    
-}
 
-//string corresponding to the instruction used
-fn instruction_name(instruction: &Instruction) -> String {
-  match instruction {
-      Instruction::ADD(target) => {
-        let str = "ADD ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A,A",
-          ArithmeticTarget::B => str + "A,B", 
-          ArithmeticTarget::C => str + "A,C",
-          ArithmeticTarget::D => str + "A,D", 
-          ArithmeticTarget::E => str + "A,E", 
-          ArithmeticTarget::H => str + "A,H", 
-          ArithmeticTarget::L => str + "A,L", 
-          ArithmeticTarget::HL => str + "A,(HL)", 
-          ArithmeticTarget::D8 => str + "A,d8", 
-          ArithmeticTarget::SP => str + " SP,r8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::ADC(target) => {
-        let str = "ADC ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A,A",
-          ArithmeticTarget::B => str + "A,B", 
-          ArithmeticTarget::C => str + "A,C",
-          ArithmeticTarget::D => str + "A,D", 
-          ArithmeticTarget::E => str + "A,E", 
-          ArithmeticTarget::H => str + "A,H", 
-          ArithmeticTarget::L => str + "A,L", 
-          ArithmeticTarget::HL => str + "A,(HL)", 
-          ArithmeticTarget::D8 => str + "A,d8", 
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::SUB(target) => {
-        let str = "SUB ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A",
-          ArithmeticTarget::B => str + "B", 
-          ArithmeticTarget::C => str + "C",
-          ArithmeticTarget::D => str + "D", 
-          ArithmeticTarget::E => str + "E", 
-          ArithmeticTarget::H => str + "H", 
-          ArithmeticTarget::L => str + "L", 
-          ArithmeticTarget::HL => str + "(HL)", 
-          ArithmeticTarget::D8 => str + "d8", 
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::SBC(target) => {
-        let str = "SBC ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A,A",
-          ArithmeticTarget::B => str + "A,B", 
-          ArithmeticTarget::C => str + "A,C",
-          ArithmeticTarget::D => str + "A,D", 
-          ArithmeticTarget::E => str + "A,E", 
-          ArithmeticTarget::H => str + "A,H", 
-          ArithmeticTarget::L => str + "A,L", 
-          ArithmeticTarget::HL => str + "A,(HL)", 
-          ArithmeticTarget::D8 => str + "A,d8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::AND(target) => {
-        let str = "AND ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A",
-          ArithmeticTarget::B => str + "B", 
-          ArithmeticTarget::C => str + "C",
-          ArithmeticTarget::D => str + "D", 
-          ArithmeticTarget::E => str + "E", 
-          ArithmeticTarget::H => str + "H", 
-          ArithmeticTarget::L => str + "L", 
-          ArithmeticTarget::HL => str + "(HL)", 
-          ArithmeticTarget::D8 => str + "d8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::OR(target) => {
-        let str = "OR ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A",
-          ArithmeticTarget::B => str + "B", 
-          ArithmeticTarget::C => str + "C",
-          ArithmeticTarget::D => str + "D", 
-          ArithmeticTarget::E => str + "E", 
-          ArithmeticTarget::H => str + "H", 
-          ArithmeticTarget::L => str + "L", 
-          ArithmeticTarget::HL => str + "(HL)", 
-          ArithmeticTarget::D8 => str + "d8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::XOR(target) => {
-        let str = "XOR ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A",
-          ArithmeticTarget::B => str + "B", 
-          ArithmeticTarget::C => str + "C",
-          ArithmeticTarget::D => str + "D", 
-          ArithmeticTarget::E => str + "E", 
-          ArithmeticTarget::H => str + "H", 
-          ArithmeticTarget::L => str + "L", 
-          ArithmeticTarget::HL => str + "(HL)", 
-          ArithmeticTarget::D8 => str + "d8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::CP(target) => {
-        let str = "CP ".to_string();
-        match target {
-          ArithmeticTarget::A => str + "A",
-          ArithmeticTarget::B => str + "B", 
-          ArithmeticTarget::C => str + "C",
-          ArithmeticTarget::D => str + "D", 
-          ArithmeticTarget::E => str + "E", 
-          ArithmeticTarget::H => str + "H", 
-          ArithmeticTarget::L => str + "L", 
-          ArithmeticTarget::HL => str + "(HL)", 
-          ArithmeticTarget::D8 => str + "d8",
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::ADDHL(target) => {
-        let str = "ADD ".to_string();
-        match target {
-          ArithmeticTarget::BC => str + "HL,BC",
-          ArithmeticTarget::DE => str + "HL,DE", 
-          ArithmeticTarget::HL => str + "HL,HL",
-          ArithmeticTarget::SP => str + "HL,SP", 
-          _ => "Err".to_string()
-        }
-      },
-      Instruction::INC(target) => {
-        let str = "INC ".to_string();
-        match target {
-          IncDecTarget::A => str + "A",
-          IncDecTarget::B => str + "B",
-          IncDecTarget::C => str + "C",
-          IncDecTarget::D => str + "D",
-          IncDecTarget::E => str + "E",
-          IncDecTarget::H => str + "H",
-          IncDecTarget::L => str + "L",
-          IncDecTarget::HLP => str + "(HL)",
-          IncDecTarget::BC => str + "BC",
-          IncDecTarget::DE => str + "DE",
-          IncDecTarget::HL => str + "HL",
-          IncDecTarget::SP => str + "SP"
-        }
-      },
-      Instruction::DEC(target) => {
-        let str = "DEC ".to_string();
-        match target {
-          IncDecTarget::A => str + "A",
-          IncDecTarget::B => str + "B",
-          IncDecTarget::C => str + "C",
-          IncDecTarget::D => str + "D",
-          IncDecTarget::E => str + "E",
-          IncDecTarget::H => str + "H",
-          IncDecTarget::L => str + "L",
-          IncDecTarget::HLP => str + "(HL)",
-          IncDecTarget::BC => str + "BC",
-          IncDecTarget::DE => str + "DE",
-          IncDecTarget::HL => str + "HL",
-          IncDecTarget::SP => str + "SP"
-        }
-      },
-      Instruction::RLC(target) => {
-        let str = "RLC ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::RRC(target) => {
-        let str = "RRC ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::RL(target) => {
-        let str = "RL ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::RR(target) => {
-        let str = "RR ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::SLA(target) => {
-        let str = "SLA ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::SRA(target) => {
-        let str = "SRA ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::SWAP(target) => {
-        let str = "SWAP ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::SRL(target) => {
-        let str = "SRL ".to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::JP(test, target) => {
-        let str = "JP ".to_string();
-        match target {
-          JumpTarget::A16 => {
-            match test {
-              JumpTest::Always => str + "a16",
-              JumpTest::Carry => str + "C,a16",
-              JumpTest::NotCarry => str + "NC,a16",
-              JumpTest::NotZero => str + "NZ,a16",
-              JumpTest::Zero => str + "Z,a16"
-            }
-          },
-          JumpTarget::HL => str + "(HL)"
-        }
-      },
-      Instruction::JR(test) => {
-        let str = "JR ".to_string();
-        match test {
-          JumpTest::Always => str + "r8",
-          JumpTest::Carry => str + "C,r8",
-          JumpTest::NotCarry => str + "NC,r8",
-          JumpTest::NotZero => str + "NZ,r8",
-          JumpTest::Zero => str + "Z,r8"
-        }
-      },
-      Instruction::CALL(test) => {
-        let str = "CALL ".to_string();
-        match test {
-          JumpTest::Always => str + "a16",
-          JumpTest::Carry => str + "C,a16",
-          JumpTest::NotCarry => str + "NC,a16",
-          JumpTest::NotZero => str + "NZ,a16",
-          JumpTest::Zero => str + "Z,a16"
-        }
-      },
-      Instruction::RET(test) => {
-        let str = "RET ".to_string();
-        match test {
-          JumpTest::Always => str,
-          JumpTest::Carry => str + "C",
-          JumpTest::NotCarry => str + "NC",
-          JumpTest::NotZero => str + "NZ",
-          JumpTest::Zero => str + "Z"
-        }
-      },
-      Instruction::LD(load_type) => {
-        let mut str = "LD ".to_string();
-        match load_type {
-          LoadType::Byte(target, source) => {
-            match target {
-              LoadByteTarget::A =>{str += "A,";},
-              LoadByteTarget::A16 => {str += "(a16),";},
-              LoadByteTarget::A8 => {str = "LDH (a8),".to_string();},
-              LoadByteTarget::B => {str += "B,";},
-              LoadByteTarget::C => {str += "C,";},
-              LoadByteTarget::D => {str += "D,";},
-              LoadByteTarget::E => {str += "E,";},
-              LoadByteTarget::H => {str += "H,";},
-              LoadByteTarget::L => {str += "L,";},
-              LoadByteTarget::HLI => {str += "(HL+),";},
-              LoadByteTarget::HLD => {str += "(HL-),";},
-              LoadByteTarget::BC => {str += "(BC),";},
-              LoadByteTarget::DE => {str += "(DE),";},
-              LoadByteTarget::HL => {str += "HL,";},
-              LoadByteTarget::SP => {str += "SP,";},
-              LoadByteTarget::FF00C => {str += "(C),";},
-            }; 
-            match source {
-              LoadByteSource::A =>{str += "A";},
-              LoadByteSource::A16 => {str += "(a16)";},
-              LoadByteSource::A8 => {str = "LDH A,(a8)".to_string();},
-              LoadByteSource::B => {str += "B";},
-              LoadByteSource::C => {str += "C";},
-              LoadByteSource::D => {str += "D";},
-              LoadByteSource::E => {str += "E";},
-              LoadByteSource::H => {str += "H";},
-              LoadByteSource::L => {str += "L";},
-              LoadByteSource::HLI => {str += "(HL+)";},
-              LoadByteSource::HLD => {str += "(HL-)";},
-              LoadByteSource::BC => {str += "(BC)";},
-              LoadByteSource::DE => {str += "(DE)";},
-              LoadByteSource::HL => {str += "(HL)";},
-              LoadByteSource::SP => {str += "SP";},
-              LoadByteSource::FF00C => {str += "(C)";},
-              LoadByteSource::D8 => {str += "d8";},
-              LoadByteSource::D16 => {str += "d16";},           
-            };
-          } 
-        };
-        str  
-      },
-      Instruction::PUSH(target) => {
-        let str = "PUSH ".to_string();
-        match target {
-          StackTarget::AF => str + "AF",
-          StackTarget::BC => str + "BC",
-          StackTarget::DE => str + "DE",
-          StackTarget::HL => str + "HL"
-        }
-      },
-      Instruction::POP(target) => {
-        let str = "POP ".to_string();
-        match target {
-          StackTarget::AF => str + "AF",
-          StackTarget::BC => str + "BC",
-          StackTarget::DE => str + "DE",
-          StackTarget::HL => str + "HL"
-        }
-      },
-      Instruction::NOP() => "NOP".to_string(),
-      Instruction::BIT(bit, target) => {
-        let str = "BIT ".to_string() + &bit.to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
-        }
-      },
-      Instruction::RES(bit, target) =>  {
-        let str = "RES ".to_string() + &bit.to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
+  #[test]
+  fn tima_is_clocked_from_the_divider() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    cpu.bus.tick(10);
+    cpu.bus.bus_write(0xFF07, 0x05);
+    //First tick on the next multiple of 16 cycles since the divider started
+    cpu.bus.tick(5);
+    assert_eq!(cpu.bus.bus_read(0xFF05), 0x00);
+    cpu.bus.tick(1);
+    assert_eq!(cpu.bus.bus_read(0xFF05), 0x01);
+    //Resetting DIV restarts the count
+    cpu.bus.tick(8);
+    cpu.bus.bus_write(0xFF04, 0x00);
+    cpu.bus.tick(15);
+    assert_eq!(cpu.bus.bus_read(0xFF05), 0x01);
+    cpu.bus.tick(1);
+    assert_eq!(cpu.bus.bus_read(0xFF05), 0x02);
+  }
+
+  #[test]
+  fn bus_accesses_fit_in_the_instruction_timing() {
+    for prefixed in [false, true] {
+      for opcode in 0..=255u8 {
+        let info = OpcodeInfo::get(opcode, prefixed);
+        if info.instruction.is_none() || (!prefixed && opcode == 0xCB) {
+          continue;
         }
-      },
-      Instruction::SET(bit, target) =>  {
-        let str = "SET ".to_string() + &bit.to_string();
-        match target {
-          PrefixTarget::A => str + "A",
-          PrefixTarget::B => str + "B",
-          PrefixTarget::C => str + "C",
-          PrefixTarget::D => str + "D",
-          PrefixTarget::E => str + "E",
-          PrefixTarget::H => str + "H",
-          PrefixTarget::L => str + "L",
-          PrefixTarget::HL => str + "(HL)",
+        //Flags set and clear to take both sides of conditional branches
+        for flags in [0x00, 0xF0] {
+          let mut cpu = CPU::flat();
+          let code: &[u8] = if prefixed { &[0xCB, opcode] } else { &[opcode] };
+          for (i, &byte) in code.iter().enumerate() {
+            cpu.bus.bus_write(0x0200 + i as u16, byte);
+          }
+          cpu.set_pc(0x0200);
+          cpu.set_sp(0xD000);
+          cpu.registers.set_hl(0xC000);
+          cpu.registers.f = flags.into();
+          cpu.step();
+          let cycles = cpu.bus.cycles as u16;
+          assert!(cycles == info.cycles as u16 || cycles == info.branch_cycles as u16, "{} took {}", info, cycles);
         }
-      },
-      Instruction::RLCA() => "RLCA".to_string(),
-      Instruction::RRCA() => "RRCA".to_string(),
-      Instruction::RLA() => "RLA".to_string(),
-      Instruction::RRA() => "RRA".to_string(),
-      Instruction::DAA() => "DAA".to_string(),
-      Instruction::CPL() => "CPL".to_string(),
-      Instruction::SCF() => "SCF".to_string(),
-      Instruction::CCF() => "CCF".to_string(),
-      Instruction::RETI() => "RETI".to_string(),
-      Instruction::HALT() => "HALT".to_string(),
-      Instruction::STOP() => "STOP".to_string(),
-      Instruction::DI() => "DI".to_string(),
-      Instruction::EI() => "EI".to_string(),
-      Instruction::PREFIX() => "PREFIX".to_string(),
-      Instruction::RST(_) => "RST".to_string(),
-  }
-}
\ No newline at end of file
+      }
+    }
+  }
+
+  #[test]
+  fn reads_see_the_timer_at_the_access_cycle() {
+    //TIMA ticks 16 cycles after the DIV reset, LDH reads it on its third M-cycle
+    for (nops, tima) in [(0, 0x00), (1, 0x01)] {
+      let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+      let source = format!("{}LDH A,($05)", "NOP\n".repeat(nops));
+      cpu.load_program(&asm::assemble(&source, 0xC000).unwrap());
+      cpu.bus.bus_write(0xFF07, 0x05);
+      cpu.bus.bus_write(0xFF04, 0x00);
+      step(&mut cpu, nops + 1);
+      assert_eq!(cpu.registers.a, tima);
+    }
+  }
+
+  #[test]
+  fn ret_cc_pops_after_checking_the_condition() {
+    //With SP on TIMA the first pop reads the timer, on the third M-cycle of RET NZ
+    for (nops, tima) in [(0, 0x00), (1, 0x01)] {
+      let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+      let source = format!("{}RET NZ", "NOP\n".repeat(nops));
+      cpu.load_program(&asm::assemble(&source, 0xC000).unwrap());
+      cpu.registers.f = 0x00.into();
+      cpu.set_sp(0xFF05);
+      cpu.bus.bus_write(0xFF07, 0x05);
+      cpu.bus.bus_write(0xFF04, 0x00);
+      step(&mut cpu, nops + 1);
+      assert_eq!(cpu.pc(), tima);
+      assert_eq!(cpu.sp(), 0xFF07);
+    }
+  }
+
+  #[test]
+  fn stat_mode_follows_the_line() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    //Line 1 starts at 456: 80 dots of OAM scan, 172 of drawing, then HBlank
+    cpu.bus.tick(ppu::CYCLES_PER_LINE as u32);
+    let mut modes = Vec::new();
+    for _ in 0..ppu::CYCLES_PER_LINE / 4 {
+      assert_eq!(cpu.bus.bus_read(0xFF44), 1);
+      modes.push(cpu.bus.bus_read(0xFF41) & 0x03);
+      cpu.bus.tick(4);
+    }
+    let expected: Vec<u8> = [(2, 20), (3, 43), (0, 51)]
+      .iter()
+      .flat_map(|&(mode, count)| std::iter::repeat_n(mode, count))
+      .collect();
+    assert_eq!(modes, expected);
+    assert_eq!(cpu.bus.bus_read(0xFF44), 2);
+  }
+
+  #[test]
+  fn stat_mode_interrupts() {
+    let mut cpu = CPU::new(Cartridge::blank(), mbc::RtcClock::Cycles).unwrap();
+    //HBlank interrupt enabled, the mode bits can't be written
+    cpu.bus.bus_write(0xFF41, 0x0B);
+    assert_eq!(cpu.bus.bus_read(0xFF41), 0x8E);
+    cpu.bus.tick(248);
+    assert_eq!(cpu.bus.interrupts.read_if() & 0x02, 0);
+    cpu.bus.tick(4);
+    assert_ne!(cpu.bus.interrupts.read_if() & 0x02, 0);
+    //VBlank with its STAT interrupt at the start of line 144
+    cpu.bus.interrupts.write_if(0);
+    cpu.bus.bus_write(0xFF41, 0x10);
+    cpu.bus.tick(144 * ppu::CYCLES_PER_LINE as u32 - 252);
+    assert_eq!(cpu.bus.bus_read(0xFF44), 144);
+    assert_eq!(cpu.bus.bus_read(0xFF41) & 0x03, 1);
+    assert_eq!(cpu.bus.interrupts.read_if() & 0x03, 0x03);
+    assert!(cpu.bus.frame_done);
+  }
+
+  #[test]
+  fn halt_bug_reads_the_next_byte_twice() {
+    //3E 14 runs as LD A,$3E then INC D
+    let mut cpu = run("HALT\nLD A,$14\nNOP");
+    cpu.bus.interrupts.enable = 0x01;
+    cpu.bus.interrupts.write_if(0x01);
+    step(&mut cpu, 3);
+    assert!(!cpu.is_halted);
+    assert_eq!((cpu.registers.a, cpu.registers.d), (0x3E, 0x01));
+    assert_eq!(cpu.pc(), 0x0103);
+  }
+}
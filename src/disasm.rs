@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use crate::instruction::{Instruction, JumpTarget, JumpTest, OpcodeInfo, Operand};
+
+//Where execution starts: reset, RST and interrupt vectors, then the cartridge entry point
+pub const ENTRY_POINTS: [u16; 14] = [
+    0x0000, 0x0008, 0x0010, 0x0018, 0x0020, 0x0028, 0x0030, 0x0038,
+    0x0040, 0x0048, 0x0050, 0x0058, 0x0060, 0x0100,
+];
+
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub info: &'static OpcodeInfo,
+    //Instruction text with the immediates filled in
+    pub text: String,
+}
+
+impl Line {
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+
+    //Address this instruction can continue at besides the next one
+    pub fn target(&self) -> Option<u16> {
+        let word = || self.bytes[1] as u16 | (self.bytes[2] as u16) << 8;
+        match self.info.instruction? {
+            Instruction::JP(_, JumpTarget::A16) | Instruction::CALL(_) => Some(word()),
+            Instruction::JR(_) => Some(self.next_address().wrapping_add(self.bytes[1] as i8 as u16)),
+            Instruction::RST(_) => self.info.operands().find_map(|o| match o {
+                Operand::Rst(vector) => Some(vector as u16),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    //False when execution never falls through to the next instruction
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self.info.instruction,
+            None | Some(Instruction::JP(JumpTest::Always, _))
+                | Some(Instruction::JR(JumpTest::Always))
+                | Some(Instruction::RET(JumpTest::Always))
+                | Some(Instruction::RETI())
+        )
+    }
+}
+
+//"0150: F0 44     LD A,($FF44)"
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(f, "{:04X}: {:<9} {}", self.address, bytes.join(" "), self.text)
+    }
+}
+
+//Decode the instruction at address, read is given the address of every byte
+pub fn decode<F: Fn(u16) -> u8>(read: F, address: u16) -> Line {
+    let opcode = read(address);
+    let (info, prefix_len) = if opcode == 0xCB {
+        (OpcodeInfo::get(read(address.wrapping_add(1)), true), 1)
+    } else {
+        (OpcodeInfo::get(opcode, false), 0)
+    };
+    let bytes: Vec<u8> = (0..info.length as u16).map(|i| read(address.wrapping_add(i))).collect();
+    let immediate = &bytes[(1 + prefix_len).min(bytes.len())..];
+    let byte = immediate.first().copied().unwrap_or(0);
+    let word = byte as u16 | (immediate.get(1).copied().unwrap_or(0) as u16) << 8;
+    let next = address.wrapping_add(info.length as u16);
+
+    let operands: Vec<String> = info
+        .operands()
+        .map(|operand| match operand {
+            Operand::D8 => format!("${:02X}", byte),
+            Operand::D16 | Operand::A16 => format!("${:04X}", word),
+            Operand::IndA16 => format!("(${:04X})", word),
+            Operand::A8 => format!("(${:04X})", 0xFF00 | byte as u16),
+            Operand::IndC => "($FF00+C)".to_string(),
+            Operand::R8 if matches!(info.instruction, Some(Instruction::JR(_))) => {
+                format!("${:04X}", next.wrapping_add(byte as i8 as u16))
+            }
+            Operand::R8 => signed(byte),
+            Operand::SPR8 => format!("SP{}", if (byte as i8) < 0 { signed(byte) } else { format!("+{}", signed(byte)) }),
+            Operand::Rst(vector) => format!("${:02X}", vector),
+            other => other.to_string(),
+        })
+        .collect();
+    //LDH is the same instruction as LD with the address written out in full
+    let mnemonic = if info.mnemonic == "LDH" { "LD" } else { info.mnemonic };
+    let text = if info.instruction.is_none() {
+        format!("DB ${:02X}", opcode)
+    } else if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(","))
+    };
+    Line { address, bytes, info, text }
+}
+
+fn signed(byte: u8) -> String {
+    let value = byte as i8;
+    if value < 0 {
+        format!("-${:02X}", value.unsigned_abs())
+    } else {
+        format!("${:02X}", value)
+    }
+}
+
+//Decode count instructions one after the other
+pub fn linear<F: Fn(u16) -> u8>(read: F, from: u16, count: usize) -> Vec<Line> {
+    let mut lines = Vec::with_capacity(count);
+    let mut address = from;
+    for _ in 0..count {
+        let line = decode(&read, address);
+        address = line.next_address();
+        lines.push(line);
+        if address == 0 {
+            break;
+        }
+    }
+    lines
+}
+
+//Follow jumps, calls and restarts from the entry points, only decoding reachable code.
+//Addresses at or above end (the ROM size, at most 0x8000) are not followed, as the
+//bank mapped at 0x4000 isn't known without running the game.
+pub fn recursive<F: Fn(u16) -> u8>(read: F, entries: &[u16], end: u16) -> BTreeMap<u16, Line> {
+    let mut lines = BTreeMap::new();
+    let mut pending: Vec<u16> = entries.to_vec();
+    let mut seen = BTreeSet::new();
+    while let Some(mut address) = pending.pop() {
+        while address < end && seen.insert(address) {
+            let line = decode(&read, address);
+            if line.next_address() > end || line.next_address() < address {
+                break;
+            }
+            if let Some(target) = line.target() {
+                pending.push(target);
+            }
+            let falls_through = line.falls_through();
+            address = line.next_address();
+            lines.insert(line.address, line);
+            if !falls_through {
+                break;
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(code: &[u8], base: u16) -> impl Fn(u16) -> u8 + '_ {
+        move |address| code.get(address.wrapping_sub(base) as usize).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn immediates_are_resolved() {
+        let code = [0xF0, 0x44, 0x20, 0xFC, 0xEA, 0x00, 0xC0, 0xCB, 0x7E, 0xE8, 0xFE, 0xD3];
+        let lines = linear(reader(&code, 0x0150), 0x0150, 6);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, ["LD A,($FF44)", "JR NZ,$0150", "LD ($C000),A", "BIT 7,(HL)", "ADD SP,-$02", "DB $D3"]);
+        assert_eq!(lines[1].to_string(), "0152: 20 FC     JR NZ,$0150");
+    }
+
+    #[test]
+    fn recursive_descent_follows_control_flow() {
+        let mut rom = vec![0x00; 0x200];
+        //0x100: JP $0150
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        //0x150: CALL $0160 / JR $0150, then data that must not be decoded
+        rom[0x150..0x156].copy_from_slice(&[0xCD, 0x60, 0x01, 0x18, 0xFB, 0xD3]);
+        //0x160: RET
+        rom[0x160] = 0xC9;
+        let lines = recursive(reader(&rom, 0), &[0x100], rom.len() as u16);
+        let addresses: Vec<u16> = lines.keys().copied().collect();
+        assert_eq!(addresses, [0x100, 0x150, 0x153, 0x160]);
+    }
+}
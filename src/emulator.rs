@@ -0,0 +1,112 @@
+use std::io;
+use log::warn;
+use std::path::{Path, PathBuf};
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::CPU;
+use crate::interrupt::Interrupt;
+use crate::joypad::Buttons;
+use crate::launch;
+use crate::mbc::RtcClock;
+use crate::save;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+//Cycles in a full frame (154 lines of 456 cycles)
+pub const CYCLES_PER_FRAME: u64 = 70224;
+const SAVE_INTERVAL_FRAMES: u64 = 60;
+
+pub struct Emulator {
+    pub cpu: CPU,
+    //Battery saves go here instead of next to the ROM when set
+    pub save_dir: Option<PathBuf>,
+    //Also used for the cartridges swapped in by load_rom
+    rtc_clock: RtcClock,
+    frames: u64,
+}
+
+impl Emulator {
+    pub fn new(cartridge: Cartridge, rtc_clock: RtcClock) -> Result<Emulator, CartridgeError> {
+        Ok(Emulator {
+            cpu: CPU::new(cartridge, rtc_clock)?,
+            save_dir: None,
+            rtc_clock,
+            frames: 0,
+        })
+    }
+
+    //Swap the cartridge, saving the previous one first
+    pub fn load_rom(&mut self, path: &Path) -> Result<(), CartridgeError> {
+        let cartridge = launch::launch(path)?;
+        if let Err(e) = self.save() {
+            warn!(target: "bus", "Couldn't write the save file: {}", e);
+        }
+        self.cpu = CPU::new(cartridge, self.rtc_clock)?;
+        self.frames = 0;
+        if let Err(e) = self.attach_battery(path) {
+            warn!(target: "bus", "Couldn't load the save file: {}", e);
+        }
+        Ok(())
+    }
+
+    //Load the battery save belonging to the ROM at rom_path
+    pub fn attach_battery(&mut self, rom_path: &Path) -> io::Result<()> {
+        let path = save::save_path(rom_path, self.save_dir.as_deref());
+        self.cpu.bus.attach_battery(path)
+    }
+
+    //Execute one instruction, returns true when it completed a frame
+    pub fn step_instruction(&mut self) -> bool {
+        self.service_interrupts();
+        self.execute_instruction()
+    }
+
+    //Jump to the pending interrupt handler, if any
+    pub fn service_interrupts(&mut self) {
+        let _ = self.cpu.interrupts();
+    }
+
+    //Execute the instruction at PC without checking for interrupts first
+    pub fn execute_instruction(&mut self) -> bool {
+        self.cpu.step();
+        self.cpu.bus.take_frame_done()
+    }
+
+    pub fn run_frame(&mut self) {
+        let start = self.cpu.bus.cycles;
+        //With the LCD off no VBlank comes, stop after a frame worth of cycles
+        //Nothing advances in STOP mode, give the frontend a chance to press a button
+        while !self.step_instruction() && !self.cpu.is_stopped && self.cpu.bus.cycles - start < CYCLES_PER_FRAME {}
+        self.frames += 1;
+        //Save about once a second if the game wrote to cartridge RAM
+        if self.frames.is_multiple_of(SAVE_INTERVAL_FRAMES) {
+            if let Err(e) = self.cpu.bus.flush_battery(false) {
+                warn!(target: "bus", "Couldn't write the save file: {}", e);
+            }
+        }
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    //One shade (0-3) per pixel
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.cpu.bus.ppu.video_buffer
+    }
+
+    //Bytes sent over the link cable since power on
+    pub fn serial_output(&self) -> &[u8] {
+        self.cpu.bus.serial.output()
+    }
+
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        if self.cpu.bus.joypad.set_buttons(buttons) {
+            self.cpu.bus.interrupts.request(Interrupt::Joypad);
+        }
+    }
+
+    //Write the battery save, if the cartridge has one
+    pub fn save(&mut self) -> io::Result<()> {
+        self.cpu.bus.flush_battery(true)
+    }
+}
@@ -1,176 +1,128 @@
-use minifb::{Key, Window, WindowOptions, KeyRepeat};
-use std::{time::{Duration, Instant}, sync::mpsc::Receiver};
-use crate::mpsc::*;
+use minifb::{Key, Window, WindowOptions, Scale};
+use std::time::{Duration, Instant};
+use emulator::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use emulator::{screenshot, Buttons, Emulator};
 
-const WIDTH: usize = 160;
-const HEIGHT: usize = 144;
+const WIDTH: usize = SCREEN_WIDTH;
+const HEIGHT: usize = SCREEN_HEIGHT;
 
 pub struct Screen{
     window: Window,
     buffer: Vec<u32>,
-    pub joypad: Joypad,
+    title: String,
+    palette: Palette,
 }
 
-#[derive(Clone)]
-pub struct Joypad {
-    pub buttons: u8,
+//Colors of the 4 shades, from lightest to darkest
+#[derive(Clone, Copy)]
+pub struct Palette(pub [u32; 4]);
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette(screenshot::GREY)
+    }
+}
+
+impl Palette {
+    //Named palette, or 4 colors written as RRGGBB,RRGGBB,RRGGBB,RRGGBB
+    pub fn parse(name: &str) -> Option<Palette> {
+        match name {
+            "grey" | "gray" => Some(Palette::default()),
+            "green" => Some(Palette([0x009BBC0F, 0x008BAC0F, 0x00306230, 0x000F380F])),
+            "pocket" => Some(Palette([0x00C4CFA1, 0x008B956D, 0x004D533C, 0x001F1F1F])),
+            _ => {
+                let colors = name
+                    .split(',')
+                    .map(|c| u32::from_str_radix(c.trim().trim_start_matches('#'), 16).ok().filter(|&c| c <= 0xFFFFFF))
+                    .collect::<Option<Vec<u32>>>()?;
+                Some(Palette(colors.try_into().ok()?))
+            }
+        }
+    }
 }
 
 impl Screen{
-    pub fn new() -> Screen{
+    pub fn new(title: &str, scale: usize, palette: Palette) -> Screen{
         let buff: Vec<u32> = vec![0; WIDTH * HEIGHT];
+        let scale = match scale {
+            1 => Scale::X1,
+            2 => Scale::X2,
+            4 => Scale::X4,
+            8 => Scale::X8,
+            16 => Scale::X16,
+            _ => Scale::X32,
+        };
         let wind = Window::new(
-            "Rust Game",
+            title,
             WIDTH,
             HEIGHT,
             WindowOptions {
                 resize: true,
+                scale,
                 ..WindowOptions::default()
             },
         ).unwrap_or_else(|e| {
             panic!("{}", e);
         });
-        let joyp = Joypad::new();
         Screen {
             window: wind,
             buffer: buff,
-            joypad: joyp
+            title: title.to_string(),
+            palette,
         }
     }
 
+    //Run the emulator until the window is closed or max_frames have been shown
+    pub fn run(&mut self, emulator: &mut Emulator, max_frames: Option<u32>) {
 
-
-    pub fn render_screen(&mut self, rx1: &Receiver<[u8;160*144]>, tx2: &Sender<Joypad>, tx3: &Sender<bool>) {
-
-    
         self.window.limit_update_rate(Some(std::time::Duration::from_micros(16600))); // ~60fps
-    
-    
+
         let mut fps_timer = Instant::now();
         let mut fps_counter = 0;
-        let mut video_buffer: [u8; 160*144];
+        let mut frames = 0;
 
-    
         while self.window.is_open() && !self.window.is_key_down(Key::Escape) {
-            match rx1.try_recv() {
-                Ok(data) => {
-                    video_buffer = data;
-                    
-                    //loop through buffer to change the pixels color
-                    let mut i = 0;
-                    while i < self.buffer.len() {
-                        let mut color_value = 0x00FFFFFF;   //default value
-                        match video_buffer[i] {
-                            0 => color_value = 0x00FFFFFF,  //white pixel
-                            1 => color_value = 0x00A9A9A9,  //light gray pixel
-                            2 => color_value = 0x00545454,  //dark gray pixel
-                            3 => color_value = 0x00000000,  //black pixel
-                            _ => println!("Wrong pixel value while updating the buffer\n"),
-                        }
-                        self.buffer[i] = color_value;
-                        i += 1;
-                    }
-                },
-                Err(TryRecvError::Disconnected) => {/* handle sender disconnected */}
-                Err(TryRecvError::Empty) => {/* handle no data available yet */}
-
+            if max_frames.is_some_and(|n| frames >= n) {
+                break;
             }
 
+            //read inputs
+            emulator.set_buttons(self.read_buttons());
+
+            emulator.run_frame();
+            frames += 1;
+
+            //loop through buffer to change the pixels color
+            for (pixel, &shade) in self.buffer.iter_mut().zip(emulator.framebuffer().iter()) {
+                *pixel = self.palette.0[(shade & 0x03) as usize];
+            }
 
-            let mut button_pressed: bool = false;
-            
-    
             // Calculate fps
             fps_counter += 1;
             let elapsed = fps_timer.elapsed();
             if elapsed >= Duration::from_secs(1) {
                 let fps = fps_counter as f64 / elapsed.as_secs_f64();
-                self.window.set_title(&format!("Rust Game (FPS: {:.2})", fps));
+                self.window.set_title(&format!("{} (FPS: {:.2})", self.title, fps));
                 fps_counter = 0;
                 fps_timer = Instant::now();
             }
 
-            //read inputs
-            self.window.get_keys_pressed(KeyRepeat::No).iter().for_each(|key|
-                match key {
-                    //button A
-                    Key::A => {self.joypad.buttons = Joypad::register_input(0, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-                    Key::W => {self.joypad.buttons = Joypad::register_input(0, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-
-                    //button B
-                    Key::B => {self.joypad.buttons = Joypad::register_input(1, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-                    Key::X => {self.joypad.buttons = Joypad::register_input(1, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-
-                    //button Select
-                    Key::R => {self.joypad.buttons = Joypad::register_input(2, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-                    Key::V => {self.joypad.buttons = Joypad::register_input(2, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-
-                    //button Start
-                    Key::E => {self.joypad.buttons = Joypad::register_input(3, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-                    Key::C => {self.joypad.buttons = Joypad::register_input(3, String::from("buttons"), self.joypad.clone()); button_pressed = true},
-
-                    //button Right
-                    Key::D => {self.joypad.buttons = Joypad::register_input(0, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-                    Key::Right => {self.joypad.buttons = Joypad::register_input(0, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-
-                    //button Left
-                    Key::Q => {self.joypad.buttons = Joypad::register_input(1, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-                    Key::Left => {self.joypad.buttons = Joypad::register_input(1, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-
-                    //button Up
-                    Key::Z => {self.joypad.buttons = Joypad::register_input(2, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-                    Key::Up => {self.joypad.buttons = Joypad::register_input(2, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-
-                    //button Down
-                    Key::S => {self.joypad.buttons = Joypad::register_input(3, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-                    Key::Down => {self.joypad.buttons = Joypad::register_input(3, String::from("dpad"), self.joypad.clone()); button_pressed = true},
-
-
-
-                    _ => (),
-                }
-            );
-            if button_pressed {
-                let _= tx2.send(self.joypad.clone());
-                let _= tx3.send(true);
-                self.joypad.buttons = 0b00111111;
-            }
-            
             // Update the window buffer and display the changes
             self.window.update_with_buffer(&self.buffer, WIDTH, HEIGHT).unwrap();
         }
     }
-    
-}
-
-impl Joypad {
-    pub fn new() -> Joypad {
-        let buttons_matrix = 0b00111111;
-        Joypad {
-            buttons: buttons_matrix,
-
-        }
-    }
 
-    pub fn register_input(button: u8, mode: String, mut joypad: Joypad) -> u8 {
-        match mode.as_str() {
-            "dpad" => {
-                if joypad.buttons & (1 << 5) != 0{
-                    let clear_bit_mask = !(1 << button); // Create a mask with the bit to clear set to 0
-                    joypad.buttons &= clear_bit_mask;
-                    joypad.buttons &= !(1 << 4);
-                }
-            },
-            "buttons" => {
-                if joypad.buttons & (1 << 4) != 0{
-                    let clear_bit_mask = !(1 << button); // Create a mask with the bit to clear set to 0
-                    joypad.buttons &= clear_bit_mask;
-                    joypad.buttons &= !(1 << 5);
-                }
-            },
-            _ => panic!("Wrong mode selection on joypad")
-            
+    fn read_buttons(&self) -> Buttons {
+        let down = |keys: &[Key]| keys.iter().any(|&key| self.window.is_key_down(key));
+        Buttons {
+            a: down(&[Key::A, Key::W]),
+            b: down(&[Key::B, Key::X]),
+            select: down(&[Key::R, Key::V]),
+            start: down(&[Key::E, Key::C]),
+            right: down(&[Key::D, Key::Right]),
+            left: down(&[Key::Q, Key::Left]),
+            up: down(&[Key::Z, Key::Up]),
+            down: down(&[Key::S, Key::Down]),
         }
-        joypad.buttons
     }
-}
\ No newline at end of file
+}
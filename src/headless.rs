@@ -0,0 +1,159 @@
+use std::io;
+use std::path::PathBuf;
+use crate::emulator::Emulator;
+use crate::joypad::Buttons;
+use crate::screenshot;
+
+//Buttons held from a given frame on, until the next entry of the script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputEvent {
+    pub frame: u64,
+    pub buttons: Buttons,
+}
+
+//Joypad input replayed frame by frame, one "<frame> <button>..." entry per line:
+//
+//  # wait for the title screen then press start
+//  120 start
+//  125
+//  300 a right
+//
+//An entry without buttons releases everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<InputEvent>,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<InputScript, String> {
+        let mut events: Vec<InputEvent> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let frame = words.next().unwrap_or("");
+            let frame: u64 = frame
+                .parse()
+                .map_err(|_| format!("line {}: invalid frame number '{}'", number + 1, frame))?;
+            if events.last().is_some_and(|e| e.frame >= frame) {
+                return Err(format!("line {}: frames must be increasing", number + 1));
+            }
+            let mut buttons = Buttons::default();
+            for word in words {
+                let button = match word.to_ascii_lowercase().as_str() {
+                    "a" => &mut buttons.a,
+                    "b" => &mut buttons.b,
+                    "select" => &mut buttons.select,
+                    "start" => &mut buttons.start,
+                    "right" => &mut buttons.right,
+                    "left" => &mut buttons.left,
+                    "up" => &mut buttons.up,
+                    "down" => &mut buttons.down,
+                    _ => return Err(format!("line {}: unknown button '{}'", number + 1, word)),
+                };
+                *button = true;
+            }
+            events.push(InputEvent { frame, buttons });
+        }
+        Ok(InputScript { events })
+    }
+
+    //Buttons held during the given frame
+    pub fn buttons_at(&self, frame: u64) -> Buttons {
+        self.events
+            .iter()
+            .take_while(|e| e.frame <= frame)
+            .last()
+            .map(|e| e.buttons)
+            .unwrap_or_default()
+    }
+}
+
+//Runs the emulator without a window, for CI and regression tests
+#[derive(Default)]
+pub struct HeadlessRunner {
+    pub frames: Option<u64>,
+    pub input: InputScript,
+    //Framebuffer written to the file once the frame has been emulated
+    pub dumps: Vec<(u64, PathBuf)>,
+    pub palette: Option<[u32; 4]>,
+}
+
+impl HeadlessRunner {
+    pub fn new() -> HeadlessRunner {
+        HeadlessRunner::default()
+    }
+
+    pub fn run(&self, emulator: &mut Emulator) -> io::Result<()> {
+        self.run_until(emulator, |_| false)
+    }
+
+    //Stops after the frame count or as soon as done returns true, checked after every frame
+    pub fn run_until<F>(&self, emulator: &mut Emulator, mut done: F) -> io::Result<()>
+    where
+        F: FnMut(&Emulator) -> bool,
+    {
+        let palette = self.palette.unwrap_or(screenshot::GREY);
+        while self.frames.is_none_or(|n| emulator.frame_count() < n) {
+            let frame = emulator.frame_count();
+            emulator.set_buttons(self.input.buttons_at(frame));
+            emulator.run_frame();
+            for (_, path) in self.dumps.iter().filter(|(f, _)| *f == frame + 1) {
+                screenshot::write(path, emulator.framebuffer(), &palette)?;
+            }
+            if done(emulator) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::mbc::RtcClock;
+
+    #[test]
+    fn script_holds_buttons_until_next_entry() {
+        let script = InputScript::parse("# title\n10 start\n12\n20 a right # jump\n").unwrap();
+        assert_eq!(script.buttons_at(0), Buttons::default());
+        assert!(script.buttons_at(10).start);
+        assert!(script.buttons_at(11).start);
+        assert_eq!(script.buttons_at(12), Buttons::default());
+        let held = script.buttons_at(500);
+        assert!(held.a && held.right && !held.start);
+    }
+
+    #[test]
+    fn script_rejects_bad_lines() {
+        assert!(InputScript::parse("x start").is_err());
+        assert!(InputScript::parse("10 turbo").is_err());
+        assert!(InputScript::parse("10 a\n5 b").is_err());
+    }
+
+    #[test]
+    fn runner_stops_after_the_frame_count_and_dumps() {
+        let mut emulator = Emulator::new(Cartridge::blank(), RtcClock::Cycles).unwrap();
+        let path = std::env::temp_dir().join(format!("emulator-dump-{}.ppm", std::process::id()));
+        let mut runner = HeadlessRunner::new();
+        runner.frames = Some(2);
+        runner.dumps = vec![(1, path.clone())];
+        runner.run(&mut emulator).unwrap();
+        assert_eq!(emulator.frame_count(), 2);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn run_until_checks_after_every_frame() {
+        let mut emulator = Emulator::new(Cartridge::blank(), RtcClock::Cycles).unwrap();
+        let mut runner = HeadlessRunner::new();
+        runner.frames = Some(10);
+        runner.run_until(&mut emulator, |e| e.frame_count() == 2).unwrap();
+        assert_eq!(emulator.frame_count(), 2);
+    }
+}
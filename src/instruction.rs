@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     // ALU Instructions
     ADD(ArithmeticTarget),
@@ -69,40 +72,50 @@ pub enum Instruction {
     RST(RestartTarget),
 }
   
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RestartTarget {
     H00, H08, H10, H18, H20, H28, H30, H38 
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArithmeticTarget {
     A, B, C, D, E, H, L, HL, D8, DE, BC, SP
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JumpTarget{
     A16, HL
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IncDecTarget{
     A,B,C,D,E,H,L, BC, DE, SP, HL, HLP
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrefixTarget {
     A,B,C,D,E,H,L,HL
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StackTarget{
     BC, DE, HL, AF
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadByteTarget{
     A, B, C, D, E, H, L, HLI, HLD, BC, A16, DE, HL, SP, FF00C, A8
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadByteSource{
     A, B, C, D, E, H, L, D8, HLI, HLD, BC, SP, DE, D16, HL, FF00C, A8, A16
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadType{
     Byte(LoadByteTarget,LoadByteSource)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JumpTest{
     NotZero,
     Zero,
@@ -113,595 +126,571 @@ pub enum JumpTest{
 
 impl Instruction{
     pub fn from_byte(byte: u8, prefixed: bool) -> Option<Instruction> {
+        OpcodeInfo::get(byte, prefixed).instruction
+    }
+}
+
+//What an operand of an instruction refers to, as written in the opcode tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    A, B, C, D, E, H, L, AF, BC, DE, HL, SP,
+    //(BC), (DE), (HL), (HL+), (HL-) and (C), the last one being 0xFF00+C
+    IndBC, IndDE, IndHL, IndHLI, IndHLD, IndC,
+    //Immediate byte and word
+    D8, D16,
+    //(a8) is 0xFF00+a8, a16 a jump target and (a16) a memory access
+    A8, A16, IndA16,
+    //Signed offset, and SP plus a signed offset
+    R8, SPR8,
+    CondNZ, CondZ, CondNC, CondC,
+    Bit(u8),
+    Rst(u8),
+}
+
+impl Operand {
+    //Bytes read after the opcode
+    pub fn immediate_size(&self) -> u8 {
+        match self {
+            Operand::D8 | Operand::A8 | Operand::R8 | Operand::SPR8 => 1,
+            Operand::D16 | Operand::A16 | Operand::IndA16 => 2,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Operand::A => "A",
+            Operand::B => "B",
+            Operand::C => "C",
+            Operand::D => "D",
+            Operand::E => "E",
+            Operand::H => "H",
+            Operand::L => "L",
+            Operand::AF => "AF",
+            Operand::BC => "BC",
+            Operand::DE => "DE",
+            Operand::HL => "HL",
+            Operand::SP => "SP",
+            Operand::IndBC => "(BC)",
+            Operand::IndDE => "(DE)",
+            Operand::IndHL => "(HL)",
+            Operand::IndHLI => "(HL+)",
+            Operand::IndHLD => "(HL-)",
+            Operand::IndC => "(C)",
+            Operand::D8 => "d8",
+            Operand::D16 => "d16",
+            Operand::A8 => "(a8)",
+            Operand::A16 => "a16",
+            Operand::IndA16 => "(a16)",
+            Operand::R8 => "r8",
+            Operand::SPR8 => "SP+r8",
+            Operand::CondNZ => "NZ",
+            Operand::CondZ => "Z",
+            Operand::CondNC => "NC",
+            Operand::CondC => "C",
+            Operand::Bit(bit) => return write!(f, "{}", bit),
+            Operand::Rst(vector) => return write!(f, "{:02X}H", vector),
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagEffect {
+    Unchanged,
+    Reset,
+    Set,
+    //Depends on the result
+    Affected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagEffects {
+    pub zero: FlagEffect,
+    pub subtract: FlagEffect,
+    pub half_carry: FlagEffect,
+    pub carry: FlagEffect,
+}
+
+impl FlagEffects {
+    //Parse the usual "ZNHC" notation: '-' unchanged, '0' reset, '1' set, a letter affected
+    const fn parse(flags: &str) -> FlagEffects {
+        let bytes = flags.as_bytes();
+        FlagEffects {
+            zero: FlagEffects::effect(bytes[0]),
+            subtract: FlagEffects::effect(bytes[1]),
+            half_carry: FlagEffects::effect(bytes[2]),
+            carry: FlagEffects::effect(bytes[3]),
+        }
+    }
+
+    const fn effect(c: u8) -> FlagEffect {
+        match c {
+            b'-' => FlagEffect::Unchanged,
+            b'0' => FlagEffect::Reset,
+            b'1' => FlagEffect::Set,
+            _ => FlagEffect::Affected,
+        }
+    }
+}
+
+impl fmt::Display for FlagEffects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (effect, name) in [(self.zero, 'Z'), (self.subtract, 'N'), (self.half_carry, 'H'), (self.carry, 'C')] {
+            let c = match effect {
+                FlagEffect::Unchanged => '-',
+                FlagEffect::Reset => '0',
+                FlagEffect::Set => '1',
+                FlagEffect::Affected => name,
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+//Everything known about an opcode, cycles are in T-cycles
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    operands: [Option<Operand>; 2],
+    //Bytes including the opcode (and the 0xCB prefix)
+    pub length: u8,
+    //Cycles when a conditional branch is not taken, or of any other instruction
+    pub cycles: u8,
+    //Cycles when a conditional branch is taken, same as cycles otherwise
+    pub branch_cycles: u8,
+    pub flags: FlagEffects,
+    //None for the 11 unused opcodes
+    pub instruction: Option<Instruction>,
+}
+
+impl OpcodeInfo {
+    pub fn get(byte: u8, prefixed: bool) -> &'static OpcodeInfo {
         if prefixed {
-          Instruction::from_byte_prefixed(byte)
+            &CB_OPCODES[byte as usize]
         } else {
-          Instruction::from_byte_not_prefixed(byte)
+            &OPCODES[byte as usize]
         }
     }
-    
-    fn from_byte_prefixed(byte: u8) -> Option<Instruction> {
-        match byte {
-            0x00 => Some(Instruction::RLC(PrefixTarget::B)),
-            0x01 => Some(Instruction::RLC(PrefixTarget::C)),
-            0x02 => Some(Instruction::RLC(PrefixTarget::D)),
-            0x03 => Some(Instruction::RLC(PrefixTarget::E)),
-            0x04 => Some(Instruction::RLC(PrefixTarget::H)),
-            0x05 => Some(Instruction::RLC(PrefixTarget::L)),
-            0x06 => Some(Instruction::RLC(PrefixTarget::HL)),
-            0x07 => Some(Instruction::RLC(PrefixTarget::A)),
-
-            0x08 => Some(Instruction::RRC(PrefixTarget::B)),
-            0x09 => Some(Instruction::RRC(PrefixTarget::C)),
-            0x0A => Some(Instruction::RRC(PrefixTarget::D)),
-            0x0B => Some(Instruction::RRC(PrefixTarget::E)),
-            0x0C => Some(Instruction::RRC(PrefixTarget::H)),
-            0x0D => Some(Instruction::RRC(PrefixTarget::L)),
-            0x0E => Some(Instruction::RRC(PrefixTarget::HL)),
-            0x0F => Some(Instruction::RRC(PrefixTarget::A)),
-
-            0x10 => Some(Instruction::RL(PrefixTarget::B)),
-            0x11 => Some(Instruction::RL(PrefixTarget::C)),
-            0x12 => Some(Instruction::RL(PrefixTarget::D)),
-            0x13 => Some(Instruction::RL(PrefixTarget::E)),
-            0x14 => Some(Instruction::RL(PrefixTarget::H)),
-            0x15 => Some(Instruction::RL(PrefixTarget::L)),
-            0x16 => Some(Instruction::RL(PrefixTarget::HL)),
-            0x17 => Some(Instruction::RL(PrefixTarget::A)),
-
-            0x18 => Some(Instruction::RR(PrefixTarget::B)),
-            0x19 => Some(Instruction::RR(PrefixTarget::C)),
-            0x1A => Some(Instruction::RR(PrefixTarget::D)),
-            0x1B => Some(Instruction::RR(PrefixTarget::E)),
-            0x1C => Some(Instruction::RR(PrefixTarget::H)),
-            0x1D => Some(Instruction::RR(PrefixTarget::L)),
-            0x1E => Some(Instruction::RR(PrefixTarget::HL)),
-            0x1F => Some(Instruction::RR(PrefixTarget::A)),
-
-            0x20 => Some(Instruction::SLA(PrefixTarget::B)),
-            0x21 => Some(Instruction::SLA(PrefixTarget::C)),
-            0x22 => Some(Instruction::SLA(PrefixTarget::D)),
-            0x23 => Some(Instruction::SLA(PrefixTarget::E)),
-            0x24 => Some(Instruction::SLA(PrefixTarget::H)),
-            0x25 => Some(Instruction::SLA(PrefixTarget::L)),
-            0x26 => Some(Instruction::SLA(PrefixTarget::HL)),
-            0x27 => Some(Instruction::SLA(PrefixTarget::A)),
-
-            0x28 => Some(Instruction::SRA(PrefixTarget::B)),
-            0x29 => Some(Instruction::SRA(PrefixTarget::C)),
-            0x2A => Some(Instruction::SRA(PrefixTarget::D)),
-            0x2B => Some(Instruction::SRA(PrefixTarget::E)),
-            0x2C => Some(Instruction::SRA(PrefixTarget::H)),
-            0x2D => Some(Instruction::SRA(PrefixTarget::L)),
-            0x2E => Some(Instruction::SRA(PrefixTarget::HL)),
-            0x2F => Some(Instruction::SRA(PrefixTarget::A)),
-
-            0x30 => Some(Instruction::SWAP(PrefixTarget::B)),
-            0x31 => Some(Instruction::SWAP(PrefixTarget::C)),
-            0x32 => Some(Instruction::SWAP(PrefixTarget::D)),
-            0x33 => Some(Instruction::SWAP(PrefixTarget::E)),
-            0x34 => Some(Instruction::SWAP(PrefixTarget::H)),
-            0x35 => Some(Instruction::SWAP(PrefixTarget::L)),
-            0x36 => Some(Instruction::SWAP(PrefixTarget::HL)),
-            0x37 => Some(Instruction::SWAP(PrefixTarget::A)),
-
-            0x38 => Some(Instruction::SRL(PrefixTarget::B)),
-            0x39 => Some(Instruction::SRL(PrefixTarget::C)),
-            0x3A => Some(Instruction::SRL(PrefixTarget::D)),
-            0x3B => Some(Instruction::SRL(PrefixTarget::E)),
-            0x3C => Some(Instruction::SRL(PrefixTarget::H)),
-            0x3D => Some(Instruction::SRL(PrefixTarget::L)),
-            0x3E => Some(Instruction::SRL(PrefixTarget::HL)),
-            0x3F => Some(Instruction::SRL(PrefixTarget::A)),
-
-            0x40 => Some(Instruction::BIT(0,PrefixTarget::B)),
-            0x41 => Some(Instruction::BIT(0,PrefixTarget::C)),
-            0x42 => Some(Instruction::BIT(0,PrefixTarget::D)),
-            0x43 => Some(Instruction::BIT(0,PrefixTarget::E)),
-            0x44 => Some(Instruction::BIT(0,PrefixTarget::H)),
-            0x45 => Some(Instruction::BIT(0,PrefixTarget::L)),
-            0x46 => Some(Instruction::BIT(0,PrefixTarget::HL)),
-            0x47 => Some(Instruction::BIT(0,PrefixTarget::A)),
-
-            0x48 => Some(Instruction::BIT(1,PrefixTarget::B)),
-            0x49 => Some(Instruction::BIT(1,PrefixTarget::C)),
-            0x4A => Some(Instruction::BIT(1,PrefixTarget::D)),
-            0x4B => Some(Instruction::BIT(1,PrefixTarget::E)),
-            0x4C => Some(Instruction::BIT(1,PrefixTarget::H)),
-            0x4D => Some(Instruction::BIT(1,PrefixTarget::L)),
-            0x4E => Some(Instruction::BIT(1,PrefixTarget::HL)),
-            0x4F => Some(Instruction::BIT(1,PrefixTarget::A)),
-
-            0x50 => Some(Instruction::BIT(2,PrefixTarget::B)),
-            0x51 => Some(Instruction::BIT(2,PrefixTarget::C)),
-            0x52 => Some(Instruction::BIT(2,PrefixTarget::D)),
-            0x53 => Some(Instruction::BIT(2,PrefixTarget::E)),
-            0x54 => Some(Instruction::BIT(2,PrefixTarget::H)),
-            0x55 => Some(Instruction::BIT(2,PrefixTarget::L)),
-            0x56 => Some(Instruction::BIT(2,PrefixTarget::HL)),
-            0x57 => Some(Instruction::BIT(2,PrefixTarget::A)),
-
-            0x58 => Some(Instruction::BIT(3,PrefixTarget::B)),
-            0x59 => Some(Instruction::BIT(3,PrefixTarget::C)),
-            0x5A => Some(Instruction::BIT(3,PrefixTarget::D)),
-            0x5B => Some(Instruction::BIT(3,PrefixTarget::E)),
-            0x5C => Some(Instruction::BIT(3,PrefixTarget::H)),
-            0x5D => Some(Instruction::BIT(3,PrefixTarget::L)),
-            0x5E => Some(Instruction::BIT(3,PrefixTarget::HL)),
-            0x5F => Some(Instruction::BIT(3,PrefixTarget::A)),
-
-            0x60 => Some(Instruction::BIT(4,PrefixTarget::B)),
-            0x61 => Some(Instruction::BIT(4,PrefixTarget::C)),
-            0x62 => Some(Instruction::BIT(4,PrefixTarget::D)),
-            0x63 => Some(Instruction::BIT(4,PrefixTarget::E)),
-            0x64 => Some(Instruction::BIT(4,PrefixTarget::H)),
-            0x65 => Some(Instruction::BIT(4,PrefixTarget::L)),
-            0x66 => Some(Instruction::BIT(4,PrefixTarget::HL)),
-            0x67 => Some(Instruction::BIT(4,PrefixTarget::A)),
-
-            0x68 => Some(Instruction::BIT(5,PrefixTarget::B)),
-            0x69 => Some(Instruction::BIT(5,PrefixTarget::C)),
-            0x6A => Some(Instruction::BIT(5,PrefixTarget::D)),
-            0x6B => Some(Instruction::BIT(5,PrefixTarget::E)),
-            0x6C => Some(Instruction::BIT(5,PrefixTarget::H)),
-            0x6D => Some(Instruction::BIT(5,PrefixTarget::L)),
-            0x6E => Some(Instruction::BIT(5,PrefixTarget::HL)),
-            0x6F => Some(Instruction::BIT(5,PrefixTarget::A)),
-
-            0x70 => Some(Instruction::BIT(6,PrefixTarget::B)),
-            0x71 => Some(Instruction::BIT(6,PrefixTarget::C)),
-            0x72 => Some(Instruction::BIT(6,PrefixTarget::D)),
-            0x73 => Some(Instruction::BIT(6,PrefixTarget::E)),
-            0x74 => Some(Instruction::BIT(6,PrefixTarget::H)),
-            0x75 => Some(Instruction::BIT(6,PrefixTarget::L)),
-            0x76 => Some(Instruction::BIT(6,PrefixTarget::HL)),
-            0x77 => Some(Instruction::BIT(6,PrefixTarget::A)),
-
-            0x78 => Some(Instruction::BIT(7,PrefixTarget::B)),
-            0x79 => Some(Instruction::BIT(7,PrefixTarget::C)),
-            0x7A => Some(Instruction::BIT(7,PrefixTarget::D)),
-            0x7B => Some(Instruction::BIT(7,PrefixTarget::E)),
-            0x7C => Some(Instruction::BIT(7,PrefixTarget::H)),
-            0x7D => Some(Instruction::BIT(7,PrefixTarget::L)),
-            0x7E => Some(Instruction::BIT(7,PrefixTarget::HL)),
-            0x7F => Some(Instruction::BIT(7,PrefixTarget::A)),
-
-            0x80 => Some(Instruction::RES(0,PrefixTarget::B)),
-            0x81 => Some(Instruction::RES(0,PrefixTarget::C)),
-            0x82 => Some(Instruction::RES(0,PrefixTarget::D)),
-            0x83 => Some(Instruction::RES(0,PrefixTarget::E)),
-            0x84 => Some(Instruction::RES(0,PrefixTarget::H)),
-            0x85 => Some(Instruction::RES(0,PrefixTarget::L)),
-            0x86 => Some(Instruction::RES(0,PrefixTarget::HL)),
-            0x87 => Some(Instruction::RES(0,PrefixTarget::A)),
-
-            0x88 => Some(Instruction::RES(1,PrefixTarget::B)),
-            0x89 => Some(Instruction::RES(1,PrefixTarget::C)),
-            0x8A => Some(Instruction::RES(1,PrefixTarget::D)),
-            0x8B => Some(Instruction::RES(1,PrefixTarget::E)),
-            0x8C => Some(Instruction::RES(1,PrefixTarget::H)),
-            0x8D => Some(Instruction::RES(1,PrefixTarget::L)),
-            0x8E => Some(Instruction::RES(1,PrefixTarget::HL)),
-            0x8F => Some(Instruction::RES(1,PrefixTarget::A)),
-
-            0x90 => Some(Instruction::RES(2,PrefixTarget::B)),
-            0x91 => Some(Instruction::RES(2,PrefixTarget::C)),
-            0x92 => Some(Instruction::RES(2,PrefixTarget::D)),
-            0x93 => Some(Instruction::RES(2,PrefixTarget::E)),
-            0x94 => Some(Instruction::RES(2,PrefixTarget::H)),
-            0x95 => Some(Instruction::RES(2,PrefixTarget::L)),
-            0x96 => Some(Instruction::RES(2,PrefixTarget::HL)),
-            0x97 => Some(Instruction::RES(2,PrefixTarget::A)),
-
-            0x98 => Some(Instruction::RES(3,PrefixTarget::B)),
-            0x99 => Some(Instruction::RES(3,PrefixTarget::C)),
-            0x9A => Some(Instruction::RES(3,PrefixTarget::D)),
-            0x9B => Some(Instruction::RES(3,PrefixTarget::E)),
-            0x9C => Some(Instruction::RES(3,PrefixTarget::H)),
-            0x9D => Some(Instruction::RES(3,PrefixTarget::L)),
-            0x9E => Some(Instruction::RES(3,PrefixTarget::HL)),
-            0x9F => Some(Instruction::RES(3,PrefixTarget::A)),
-
-            0xA0 => Some(Instruction::RES(4,PrefixTarget::B)),
-            0xA1 => Some(Instruction::RES(4,PrefixTarget::C)),
-            0xA2 => Some(Instruction::RES(4,PrefixTarget::D)),
-            0xA3 => Some(Instruction::RES(4,PrefixTarget::E)),
-            0xA4 => Some(Instruction::RES(4,PrefixTarget::H)),
-            0xA5 => Some(Instruction::RES(4,PrefixTarget::L)),
-            0xA6 => Some(Instruction::RES(4,PrefixTarget::HL)),
-            0xA7 => Some(Instruction::RES(4,PrefixTarget::A)),
-
-            0xA8 => Some(Instruction::RES(5,PrefixTarget::B)),
-            0xA9 => Some(Instruction::RES(5,PrefixTarget::C)),
-            0xAA => Some(Instruction::RES(5,PrefixTarget::D)),
-            0xAB => Some(Instruction::RES(5,PrefixTarget::E)),
-            0xAC => Some(Instruction::RES(5,PrefixTarget::H)),
-            0xAD => Some(Instruction::RES(5,PrefixTarget::L)),
-            0xAE => Some(Instruction::RES(5,PrefixTarget::HL)),
-            0xAF => Some(Instruction::RES(5,PrefixTarget::A)),
-
-            0xB0 => Some(Instruction::RES(6,PrefixTarget::B)),
-            0xB1 => Some(Instruction::RES(6,PrefixTarget::C)),
-            0xB2 => Some(Instruction::RES(6,PrefixTarget::D)),
-            0xB3 => Some(Instruction::RES(6,PrefixTarget::E)),
-            0xB4 => Some(Instruction::RES(6,PrefixTarget::H)),
-            0xB5 => Some(Instruction::RES(6,PrefixTarget::L)),
-            0xB6 => Some(Instruction::RES(6,PrefixTarget::HL)),
-            0xB7 => Some(Instruction::RES(6,PrefixTarget::A)),
-
-            0xB8 => Some(Instruction::RES(7,PrefixTarget::B)),
-            0xB9 => Some(Instruction::RES(7,PrefixTarget::C)),
-            0xBA => Some(Instruction::RES(7,PrefixTarget::D)),
-            0xBB => Some(Instruction::RES(7,PrefixTarget::E)),
-            0xBC => Some(Instruction::RES(7,PrefixTarget::H)),
-            0xBD => Some(Instruction::RES(7,PrefixTarget::L)),
-            0xBE => Some(Instruction::RES(7,PrefixTarget::HL)),
-            0xBF => Some(Instruction::RES(7,PrefixTarget::A)),
-
-            0xC0 => Some(Instruction::SET(0,PrefixTarget::B)),
-            0xC1 => Some(Instruction::SET(0,PrefixTarget::C)),
-            0xC2 => Some(Instruction::SET(0,PrefixTarget::D)),
-            0xC3 => Some(Instruction::SET(0,PrefixTarget::E)),
-            0xC4 => Some(Instruction::SET(0,PrefixTarget::H)),
-            0xC5 => Some(Instruction::SET(0,PrefixTarget::L)),
-            0xC6 => Some(Instruction::SET(0,PrefixTarget::HL)),
-            0xC7 => Some(Instruction::SET(0,PrefixTarget::A)),
-
-            0xC8 => Some(Instruction::SET(1,PrefixTarget::B)),
-            0xC9 => Some(Instruction::SET(1,PrefixTarget::C)),
-            0xCA => Some(Instruction::SET(1,PrefixTarget::D)),
-            0xCB => Some(Instruction::SET(1,PrefixTarget::E)),
-            0xCC => Some(Instruction::SET(1,PrefixTarget::H)),
-            0xCD => Some(Instruction::SET(1,PrefixTarget::L)),
-            0xCE => Some(Instruction::SET(1,PrefixTarget::HL)),
-            0xCF => Some(Instruction::SET(1,PrefixTarget::A)),
-
-            0xD0 => Some(Instruction::SET(2,PrefixTarget::B)),
-            0xD1 => Some(Instruction::SET(2,PrefixTarget::C)),
-            0xD2 => Some(Instruction::SET(2,PrefixTarget::D)),
-            0xD3 => Some(Instruction::SET(2,PrefixTarget::E)),
-            0xD4 => Some(Instruction::SET(2,PrefixTarget::H)),
-            0xD5 => Some(Instruction::SET(2,PrefixTarget::L)),
-            0xD6 => Some(Instruction::SET(2,PrefixTarget::HL)),
-            0xD7 => Some(Instruction::SET(2,PrefixTarget::A)),
-
-            0xD8 => Some(Instruction::SET(3,PrefixTarget::B)),
-            0xD9 => Some(Instruction::SET(3,PrefixTarget::C)),
-            0xDA => Some(Instruction::SET(3,PrefixTarget::D)),
-            0xDB => Some(Instruction::SET(3,PrefixTarget::E)),
-            0xDC => Some(Instruction::SET(3,PrefixTarget::H)),
-            0xDD => Some(Instruction::SET(3,PrefixTarget::L)),
-            0xDE => Some(Instruction::SET(3,PrefixTarget::HL)),
-            0xDF => Some(Instruction::SET(3,PrefixTarget::A)),
-
-            0xE0 => Some(Instruction::SET(4,PrefixTarget::B)),
-            0xE1 => Some(Instruction::SET(4,PrefixTarget::C)),
-            0xE2 => Some(Instruction::SET(4,PrefixTarget::D)),
-            0xE3 => Some(Instruction::SET(4,PrefixTarget::E)),
-            0xE4 => Some(Instruction::SET(4,PrefixTarget::H)),
-            0xE5 => Some(Instruction::SET(4,PrefixTarget::L)),
-            0xE6 => Some(Instruction::SET(4,PrefixTarget::HL)),
-            0xE7 => Some(Instruction::SET(4,PrefixTarget::A)),
-
-            0xE8 => Some(Instruction::SET(5,PrefixTarget::B)),
-            0xE9 => Some(Instruction::SET(5,PrefixTarget::C)),
-            0xEA => Some(Instruction::SET(5,PrefixTarget::D)),
-            0xEB => Some(Instruction::SET(5,PrefixTarget::E)),
-            0xEC => Some(Instruction::SET(5,PrefixTarget::H)),
-            0xED => Some(Instruction::SET(5,PrefixTarget::L)),
-            0xEE => Some(Instruction::SET(5,PrefixTarget::HL)),
-            0xEF => Some(Instruction::SET(5,PrefixTarget::A)),
-
-            0xF0 => Some(Instruction::SET(6,PrefixTarget::B)),
-            0xF1 => Some(Instruction::SET(6,PrefixTarget::C)),
-            0xF2 => Some(Instruction::SET(6,PrefixTarget::D)),
-            0xF3 => Some(Instruction::SET(6,PrefixTarget::E)),
-            0xF4 => Some(Instruction::SET(6,PrefixTarget::H)),
-            0xF5 => Some(Instruction::SET(6,PrefixTarget::L)),
-            0xF6 => Some(Instruction::SET(6,PrefixTarget::HL)),
-            0xF7 => Some(Instruction::SET(6,PrefixTarget::A)),
-
-            0xF8 => Some(Instruction::SET(7,PrefixTarget::B)),
-            0xF9 => Some(Instruction::SET(7,PrefixTarget::C)),
-            0xFA => Some(Instruction::SET(7,PrefixTarget::D)),
-            0xFB => Some(Instruction::SET(7,PrefixTarget::E)),
-            0xFC => Some(Instruction::SET(7,PrefixTarget::H)),
-            0xFD => Some(Instruction::SET(7,PrefixTarget::L)),
-            0xFE => Some(Instruction::SET(7,PrefixTarget::HL)),
-            0xFF => Some(Instruction::SET(7,PrefixTarget::A)),
+
+    pub fn operands(&self) -> impl Iterator<Item = Operand> {
+        self.operands.into_iter().flatten()
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.cycles != self.branch_cycles
+    }
+}
+
+//"LD A,(a16)", the notation of the usual opcode tables
+impl fmt::Display for OpcodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic)?;
+        for (i, operand) in self.operands().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { "," }, operand)?;
         }
+        Ok(())
     }
+}
+
+const fn op(
+    mnemonic: &'static str,
+    operands: &[Operand],
+    length: u8,
+    cycles: u8,
+    branch_cycles: u8,
+    flags: &str,
+    instruction: Option<Instruction>,
+) -> OpcodeInfo {
+    let mut list = [None, None];
+    let mut i = 0;
+    while i < operands.len() {
+        list[i] = Some(operands[i]);
+        i += 1;
+    }
+    OpcodeInfo {
+        mnemonic,
+        operands: list,
+        length,
+        cycles,
+        branch_cycles,
+        flags: FlagEffects::parse(flags),
+        instruction,
+    }
+}
 
-    pub fn from_byte_not_prefixed(byte: u8) -> Option<Instruction> {
-        match byte {
-            0x00 => Some(Instruction::NOP()),
-            0x01 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::BC, LoadByteSource::D16))),
-            0x02 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::BC, LoadByteSource::A))),
-            0x03 => Some(Instruction::INC(IncDecTarget::BC)),
-            0x04 => Some(Instruction::INC(IncDecTarget::B)),
-            0x05 => Some(Instruction::DEC(IncDecTarget::B)),
-            0x06 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::D8))),
-            0x07 => Some(Instruction::RLCA()),
-
-            0x08 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A16, LoadByteSource::SP))),
-            0x09 => Some(Instruction::ADDHL(ArithmeticTarget::BC)),
-            0x0A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::BC))),
-            0x0B => Some(Instruction::DEC(IncDecTarget::BC)),
-            0x0C => Some(Instruction::INC(IncDecTarget::C)),
-            0x0D => Some(Instruction::DEC(IncDecTarget::C)),
-            0x0E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::D8))),
-            0x0F => Some(Instruction::RRCA()),
-
-            0x10 => Some(Instruction::STOP()),
-            0x11 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::DE, LoadByteSource::D16))),
-            0x12 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::DE, LoadByteSource::A))),
-            0x13 => Some(Instruction::INC(IncDecTarget::DE)),
-            0x14 => Some(Instruction::INC(IncDecTarget::D)),
-            0x15 => Some(Instruction::DEC(IncDecTarget::D)),
-            0x16 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::D8))),
-            0x17 => Some(Instruction::RLA()),
-
-            0x18 => Some(Instruction::JR(JumpTest::Always)),
-            0x19 => Some(Instruction::ADDHL(ArithmeticTarget::DE)),
-            0x1A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::DE))),
-            0x1B => Some(Instruction::DEC(IncDecTarget::DE)),
-            0x1C => Some(Instruction::INC(IncDecTarget::E)),
-            0x1D => Some(Instruction::DEC(IncDecTarget::E)),
-            0x1E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::D8))),
-            0x1F => Some(Instruction::RRA()),
-
-            0x20 => Some(Instruction::JR(JumpTest::NotZero)),
-            0x21 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::D16))),
-            0x22 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSource::A))),
-            0x23 => Some(Instruction::INC(IncDecTarget::HL)),
-            0x24 => Some(Instruction::INC(IncDecTarget::H)),
-            0x25 => Some(Instruction::DEC(IncDecTarget::H)),
-            0x26 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::D8))),
-            0x27 => Some(Instruction::DAA()),
-
-            0x28 => Some(Instruction::JR(JumpTest::Zero)),
-            0x29 => Some(Instruction::ADDHL(ArithmeticTarget::HL)),
-            0x2A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::HLI))),
-            0x2B => Some(Instruction::DEC(IncDecTarget::HL)),
-            0x2C => Some(Instruction::INC(IncDecTarget::L)),
-            0x2D => Some(Instruction::DEC(IncDecTarget::L)),
-            0x2E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::D8))),
-            0x2F => Some(Instruction::CPL()),
-
-            0x30 => Some(Instruction::JR(JumpTest::NotCarry)),
-            0x31 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::SP, LoadByteSource::D16))),
-            0x32 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLD, LoadByteSource::A))),
-            0x33 => Some(Instruction::INC(IncDecTarget::SP)),
-            0x34 => Some(Instruction::INC(IncDecTarget::HLP)),
-            0x35 => Some(Instruction::DEC(IncDecTarget::HLP)),
-            0x36 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::D8))),
-            0x37 => Some(Instruction::SCF()),
-
-            0x38 => Some(Instruction::JR(JumpTest::Carry)),
-            0x39 => Some(Instruction::ADDHL(ArithmeticTarget::SP)),
-            0x3A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::HLD))),
-            0x3B => Some(Instruction::DEC(IncDecTarget::SP)),
-            0x3C => Some(Instruction::INC(IncDecTarget::A)),
-            0x3D => Some(Instruction::DEC(IncDecTarget::A)),
-            0x3E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::D8))),
-            0x3F => Some(Instruction::CCF()),
-
-            0x40 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::B))),
-            0x41 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::C))),
-            0x42 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::D))),
-            0x43 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::E))),
-            0x44 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::H))),
-            0x45 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::L))),
-            0x46 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::HL))),
-            0x47 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSource::A))),
-
-            0x48 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::B))),
-            0x49 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::C))),
-            0x4A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::D))),
-            0x4B => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::E))),
-            0x4C => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::H))),
-            0x4D => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::L))),
-            0x4E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::HL))),
-            0x4F => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSource::A))),
-
-            0x50 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::B))),
-            0x51 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::C))),
-            0x52 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::D))),
-            0x53 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::E))),
-            0x54 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::H))),
-            0x55 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::L))),
-            0x56 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::HL))),
-            0x57 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSource::A))),
-
-            0x58 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::B))),
-            0x59 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::C))),
-            0x5A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::D))),
-            0x5B => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::E))),
-            0x5C => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::H))),
-            0x5D => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::L))),
-            0x5E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::HL))),
-            0x5F => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSource::A))),
-
-            0x60 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::B))),
-            0x61 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::C))),
-            0x62 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::D))),
-            0x63 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::E))),
-            0x64 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::H))),
-            0x65 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::L))),
-            0x66 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::HL))),
-            0x67 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSource::A))),
-
-            0x68 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::B))),
-            0x69 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::C))),
-            0x6A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::D))),
-            0x6B => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::E))),
-            0x6C => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::H))),
-            0x6D => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::L))),
-            0x6E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::HL))),
-            0x6F => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSource::A))),
-
-            0x70 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::B))),
-            0x71 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::C))),
-            0x72 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::D))),
-            0x73 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::E))),
-            0x74 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::H))),
-            0x75 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::L))),
-            0x76 => Some(Instruction::HALT()),
-            0x77 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::A))),
-
-            0x78 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::B))),
-            0x79 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::C))),
-            0x7A => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::D))),
-            0x7B => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::E))),
-            0x7C => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::H))),
-            0x7D => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::L))),
-            0x7E => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::HL))),
-            0x7F => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::A))),
-
-            0x80 => Some(Instruction::ADD(ArithmeticTarget::B)),
-            0x81 => Some(Instruction::ADD(ArithmeticTarget::C)),
-            0x82 => Some(Instruction::ADD(ArithmeticTarget::D)),
-            0x83 => Some(Instruction::ADD(ArithmeticTarget::E)),
-            0x84 => Some(Instruction::ADD(ArithmeticTarget::H)),
-            0x85 => Some(Instruction::ADD(ArithmeticTarget::L)),
-            0x86 => Some(Instruction::ADD(ArithmeticTarget::HL)),
-            0x87 => Some(Instruction::ADD(ArithmeticTarget::A)),
-
-            0x88 => Some(Instruction::ADC(ArithmeticTarget::B)),
-            0x89 => Some(Instruction::ADC(ArithmeticTarget::C)),
-            0x8A => Some(Instruction::ADC(ArithmeticTarget::D)),
-            0x8B => Some(Instruction::ADC(ArithmeticTarget::E)),
-            0x8C => Some(Instruction::ADC(ArithmeticTarget::H)),
-            0x8D => Some(Instruction::ADC(ArithmeticTarget::L)),
-            0x8E => Some(Instruction::ADC(ArithmeticTarget::HL)),
-            0x8F => Some(Instruction::ADC(ArithmeticTarget::A)),
-
-            0x90 => Some(Instruction::SUB(ArithmeticTarget::B)),
-            0x91 => Some(Instruction::SUB(ArithmeticTarget::C)),
-            0x92 => Some(Instruction::SUB(ArithmeticTarget::D)),
-            0x93 => Some(Instruction::SUB(ArithmeticTarget::E)),
-            0x94 => Some(Instruction::SUB(ArithmeticTarget::H)),
-            0x95 => Some(Instruction::SUB(ArithmeticTarget::L)),
-            0x96 => Some(Instruction::SUB(ArithmeticTarget::HL)),
-            0x97 => Some(Instruction::SUB(ArithmeticTarget::A)),
-
-            0x98 => Some(Instruction::SBC(ArithmeticTarget::B)),
-            0x99 => Some(Instruction::SBC(ArithmeticTarget::C)),
-            0x9A => Some(Instruction::SBC(ArithmeticTarget::D)),
-            0x9B => Some(Instruction::SBC(ArithmeticTarget::E)),
-            0x9C => Some(Instruction::SBC(ArithmeticTarget::H)),
-            0x9D => Some(Instruction::SBC(ArithmeticTarget::L)),
-            0x9E => Some(Instruction::SBC(ArithmeticTarget::HL)),
-            0x9F => Some(Instruction::SBC(ArithmeticTarget::A)),
-
-            0xA0 => Some(Instruction::AND(ArithmeticTarget::B)),
-            0xA1 => Some(Instruction::AND(ArithmeticTarget::C)),
-            0xA2 => Some(Instruction::AND(ArithmeticTarget::D)),
-            0xA3 => Some(Instruction::AND(ArithmeticTarget::E)),
-            0xA4 => Some(Instruction::AND(ArithmeticTarget::H)),
-            0xA5 => Some(Instruction::AND(ArithmeticTarget::L)),
-            0xA6 => Some(Instruction::AND(ArithmeticTarget::HL)),
-            0xA7 => Some(Instruction::AND(ArithmeticTarget::A)),
-
-            0xA8 => Some(Instruction::XOR(ArithmeticTarget::B)),
-            0xA9 => Some(Instruction::XOR(ArithmeticTarget::C)),
-            0xAA => Some(Instruction::XOR(ArithmeticTarget::D)),
-            0xAB => Some(Instruction::XOR(ArithmeticTarget::E)),
-            0xAC => Some(Instruction::XOR(ArithmeticTarget::H)),
-            0xAD => Some(Instruction::XOR(ArithmeticTarget::HL)),
-            0xAE => Some(Instruction::XOR(ArithmeticTarget::L)),
-            0xAF => Some(Instruction::XOR(ArithmeticTarget::A)),
-
-            0xB0 => Some(Instruction::OR(ArithmeticTarget::B)),
-            0xB1 => Some(Instruction::OR(ArithmeticTarget::C)),
-            0xB2 => Some(Instruction::OR(ArithmeticTarget::D)),
-            0xB3 => Some(Instruction::OR(ArithmeticTarget::E)),
-            0xB4 => Some(Instruction::OR(ArithmeticTarget::H)),
-            0xB5 => Some(Instruction::OR(ArithmeticTarget::HL)),
-            0xB6 => Some(Instruction::OR(ArithmeticTarget::L)),
-            0xB7 => Some(Instruction::OR(ArithmeticTarget::A)),
-
-            0xB8 => Some(Instruction::CP(ArithmeticTarget::B)),
-            0xB9 => Some(Instruction::CP(ArithmeticTarget::C)),
-            0xBA => Some(Instruction::CP(ArithmeticTarget::D)),
-            0xBB => Some(Instruction::CP(ArithmeticTarget::E)),
-            0xBC => Some(Instruction::CP(ArithmeticTarget::H)),
-            0xBD => Some(Instruction::CP(ArithmeticTarget::HL)),
-            0xBE => Some(Instruction::CP(ArithmeticTarget::L)),
-            0xBF => Some(Instruction::CP(ArithmeticTarget::A)),
-
-            0xC0 => Some(Instruction::RET(JumpTest::NotZero)),
-            0xC1 => Some(Instruction::POP(StackTarget::BC)),
-            0xC2 => Some(Instruction::JP(JumpTest::NotZero,JumpTarget::A16)),
-            0xC3 => Some(Instruction::JP(JumpTest::Always,JumpTarget::A16)),
-            0xC4 => Some(Instruction::CALL(JumpTest::NotZero)),
-            0xC5 => Some(Instruction::PUSH(StackTarget::BC)),
-            0xC6 => Some(Instruction::ADD(ArithmeticTarget::D8)),
-            0xC7 => Some(Instruction::RST(RestartTarget::H00)),
-
-            0xC8 => Some(Instruction::RET(JumpTest::Zero)),
-            0xC9 => Some(Instruction::RET(JumpTest::Always)),
-            0xCA => Some(Instruction::JP(JumpTest::Zero,JumpTarget::A16)),
-            0xCB => Some(Instruction::PREFIX()),
-            0xCC => Some(Instruction::CALL(JumpTest::Zero)),
-            0xCD => Some(Instruction::CALL(JumpTest::Always)),
-            0xCE => Some(Instruction::ADC(ArithmeticTarget::D8)),
-            0xCF => Some(Instruction::RST(RestartTarget::H08)),
-
-            0xD0 => Some(Instruction::RET(JumpTest::NotCarry)),
-            0xD1 => Some(Instruction::POP(StackTarget::DE)),
-            0xD2 => Some(Instruction::JP(JumpTest::NotCarry,JumpTarget::A16)),
-            0xD3 => /* INVALID */ None,
-            0xD4 => Some(Instruction::CALL(JumpTest::NotCarry)),
-            0xD5 => Some(Instruction::PUSH(StackTarget::DE)),
-            0xD6 => Some(Instruction::SUB(ArithmeticTarget::D8)),
-            0xD7 => Some(Instruction::RST(RestartTarget::H10)),
-
-            0xD8 => Some(Instruction::RET(JumpTest::Carry)),
-            0xD9 => Some(Instruction::RETI()),
-            0xDA => Some(Instruction::JP(JumpTest::Carry,JumpTarget::A16)),
-            0xDB => /* INVALID */ None,
-            0xDC => Some(Instruction::CALL(JumpTest::Carry)),
-            0xDD => /* INVALID */ None,
-            0xDE => Some(Instruction::SBC(ArithmeticTarget::D8)),
-            0xDF => Some(Instruction::RST(RestartTarget::H18)),
-
-            0xE0 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A8, LoadByteSource::A))),
-            0xE1 => Some(Instruction::POP(StackTarget::HL)),
-            0xE2 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::FF00C, LoadByteSource::A))),
-            0xE3 => /* INVALID */ None,
-            0xE4 => /* INVALID */ None,
-            0xE5 => Some(Instruction::PUSH(StackTarget::HL)),
-            0xE6 => Some(Instruction::AND(ArithmeticTarget::D8)),
-            0xE7 => Some(Instruction::RST(RestartTarget::H20)),
-
-            0xE8 => Some(Instruction::ADD(ArithmeticTarget::SP)),
-            0xE9 => Some(Instruction::JP(JumpTest::Always,JumpTarget::HL)),
-            0xEA => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A16, LoadByteSource::A))),
-            0xEB => /* INVALID */ None,
-            0xEC => /* INVALID */ None,
-            0xED => /* INVALID */ None,
-            0xEE => Some(Instruction::XOR(ArithmeticTarget::D8)),
-            0xEF => Some(Instruction::RST(RestartTarget::H28)),
-
-            0xF0 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::A8))),
-            0xF1 => Some(Instruction::POP(StackTarget::AF)),
-            0xF2 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::FF00C))),
-            0xF3 => Some(Instruction::DI()),
-            0xF4 => /* INVALID */ None,
-            0xF5 => Some(Instruction::PUSH(StackTarget::AF)),
-            0xF6 => Some(Instruction::OR(ArithmeticTarget::D8)),
-            0xF7 => Some(Instruction::RST(RestartTarget::H30)),
-
-            0xF8 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HL, LoadByteSource::SP))),
-            0xF9 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::SP, LoadByteSource::HL))),
-            0xFA => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSource::A16))),
-            0xFB => Some(Instruction::EI()),
-            0xFC => /* INVALID */ None,
-            0xFD => /* INVALID */ None,
-            0xFE => Some(Instruction::CP(ArithmeticTarget::D8)),
-            0xFF => Some(Instruction::RST(RestartTarget::H38)),
+const fn ld(target: LoadByteTarget, source: LoadByteSource) -> Option<Instruction> {
+    Some(Instruction::LD(LoadType::Byte(target, source)))
+}
+
+const INVALID: OpcodeInfo = op("INVALID", &[], 1, 4, 4, "----", None);
+
+use self::ArithmeticTarget as AT;
+use self::IncDecTarget as IDT;
+use self::Instruction as I;
+use self::JumpTarget as JTG;
+use self::JumpTest as JT;
+use self::LoadByteSource as Src;
+use self::LoadByteTarget as To;
+use self::Operand::*;
+use self::RestartTarget as RT;
+use self::StackTarget as ST;
+
+static OPCODES: [OpcodeInfo; 256] = [
+    /* 0x00 */ op("NOP", &[], 1, 4, 4, "----", Some(I::NOP())),
+    /* 0x01 */ op("LD", &[BC, D16], 3, 12, 12, "----", ld(To::BC, Src::D16)),
+    /* 0x02 */ op("LD", &[IndBC, A], 1, 8, 8, "----", ld(To::BC, Src::A)),
+    /* 0x03 */ op("INC", &[BC], 1, 8, 8, "----", Some(I::INC(IDT::BC))),
+    /* 0x04 */ op("INC", &[B], 1, 4, 4, "Z0H-", Some(I::INC(IDT::B))),
+    /* 0x05 */ op("DEC", &[B], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::B))),
+    /* 0x06 */ op("LD", &[B, D8], 2, 8, 8, "----", ld(To::B, Src::D8)),
+    /* 0x07 */ op("RLCA", &[], 1, 4, 4, "000C", Some(I::RLCA())),
+    /* 0x08 */ op("LD", &[IndA16, SP], 3, 20, 20, "----", ld(To::A16, Src::SP)),
+    /* 0x09 */ op("ADD", &[HL, BC], 1, 8, 8, "-0HC", Some(I::ADDHL(AT::BC))),
+    /* 0x0A */ op("LD", &[A, IndBC], 1, 8, 8, "----", ld(To::A, Src::BC)),
+    /* 0x0B */ op("DEC", &[BC], 1, 8, 8, "----", Some(I::DEC(IDT::BC))),
+    /* 0x0C */ op("INC", &[C], 1, 4, 4, "Z0H-", Some(I::INC(IDT::C))),
+    /* 0x0D */ op("DEC", &[C], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::C))),
+    /* 0x0E */ op("LD", &[C, D8], 2, 8, 8, "----", ld(To::C, Src::D8)),
+    /* 0x0F */ op("RRCA", &[], 1, 4, 4, "000C", Some(I::RRCA())),
+    /* 0x10 */ op("STOP", &[], 2, 4, 4, "----", Some(I::STOP())),
+    /* 0x11 */ op("LD", &[DE, D16], 3, 12, 12, "----", ld(To::DE, Src::D16)),
+    /* 0x12 */ op("LD", &[IndDE, A], 1, 8, 8, "----", ld(To::DE, Src::A)),
+    /* 0x13 */ op("INC", &[DE], 1, 8, 8, "----", Some(I::INC(IDT::DE))),
+    /* 0x14 */ op("INC", &[D], 1, 4, 4, "Z0H-", Some(I::INC(IDT::D))),
+    /* 0x15 */ op("DEC", &[D], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::D))),
+    /* 0x16 */ op("LD", &[D, D8], 2, 8, 8, "----", ld(To::D, Src::D8)),
+    /* 0x17 */ op("RLA", &[], 1, 4, 4, "000C", Some(I::RLA())),
+    /* 0x18 */ op("JR", &[R8], 2, 12, 12, "----", Some(I::JR(JT::Always))),
+    /* 0x19 */ op("ADD", &[HL, DE], 1, 8, 8, "-0HC", Some(I::ADDHL(AT::DE))),
+    /* 0x1A */ op("LD", &[A, IndDE], 1, 8, 8, "----", ld(To::A, Src::DE)),
+    /* 0x1B */ op("DEC", &[DE], 1, 8, 8, "----", Some(I::DEC(IDT::DE))),
+    /* 0x1C */ op("INC", &[E], 1, 4, 4, "Z0H-", Some(I::INC(IDT::E))),
+    /* 0x1D */ op("DEC", &[E], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::E))),
+    /* 0x1E */ op("LD", &[E, D8], 2, 8, 8, "----", ld(To::E, Src::D8)),
+    /* 0x1F */ op("RRA", &[], 1, 4, 4, "000C", Some(I::RRA())),
+    /* 0x20 */ op("JR", &[CondNZ, R8], 2, 8, 12, "----", Some(I::JR(JT::NotZero))),
+    /* 0x21 */ op("LD", &[HL, D16], 3, 12, 12, "----", ld(To::HL, Src::D16)),
+    /* 0x22 */ op("LD", &[IndHLI, A], 1, 8, 8, "----", ld(To::HLI, Src::A)),
+    /* 0x23 */ op("INC", &[HL], 1, 8, 8, "----", Some(I::INC(IDT::HL))),
+    /* 0x24 */ op("INC", &[H], 1, 4, 4, "Z0H-", Some(I::INC(IDT::H))),
+    /* 0x25 */ op("DEC", &[H], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::H))),
+    /* 0x26 */ op("LD", &[H, D8], 2, 8, 8, "----", ld(To::H, Src::D8)),
+    /* 0x27 */ op("DAA", &[], 1, 4, 4, "Z-0C", Some(I::DAA())),
+    /* 0x28 */ op("JR", &[CondZ, R8], 2, 8, 12, "----", Some(I::JR(JT::Zero))),
+    /* 0x29 */ op("ADD", &[HL, HL], 1, 8, 8, "-0HC", Some(I::ADDHL(AT::HL))),
+    /* 0x2A */ op("LD", &[A, IndHLI], 1, 8, 8, "----", ld(To::A, Src::HLI)),
+    /* 0x2B */ op("DEC", &[HL], 1, 8, 8, "----", Some(I::DEC(IDT::HL))),
+    /* 0x2C */ op("INC", &[L], 1, 4, 4, "Z0H-", Some(I::INC(IDT::L))),
+    /* 0x2D */ op("DEC", &[L], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::L))),
+    /* 0x2E */ op("LD", &[L, D8], 2, 8, 8, "----", ld(To::L, Src::D8)),
+    /* 0x2F */ op("CPL", &[], 1, 4, 4, "-11-", Some(I::CPL())),
+    /* 0x30 */ op("JR", &[CondNC, R8], 2, 8, 12, "----", Some(I::JR(JT::NotCarry))),
+    /* 0x31 */ op("LD", &[SP, D16], 3, 12, 12, "----", ld(To::SP, Src::D16)),
+    /* 0x32 */ op("LD", &[IndHLD, A], 1, 8, 8, "----", ld(To::HLD, Src::A)),
+    /* 0x33 */ op("INC", &[SP], 1, 8, 8, "----", Some(I::INC(IDT::SP))),
+    /* 0x34 */ op("INC", &[IndHL], 1, 12, 12, "Z0H-", Some(I::INC(IDT::HLP))),
+    /* 0x35 */ op("DEC", &[IndHL], 1, 12, 12, "Z1H-", Some(I::DEC(IDT::HLP))),
+    /* 0x36 */ op("LD", &[IndHL, D8], 2, 12, 12, "----", ld(To::HL, Src::D8)),
+    /* 0x37 */ op("SCF", &[], 1, 4, 4, "-001", Some(I::SCF())),
+    /* 0x38 */ op("JR", &[CondC, R8], 2, 8, 12, "----", Some(I::JR(JT::Carry))),
+    /* 0x39 */ op("ADD", &[HL, SP], 1, 8, 8, "-0HC", Some(I::ADDHL(AT::SP))),
+    /* 0x3A */ op("LD", &[A, IndHLD], 1, 8, 8, "----", ld(To::A, Src::HLD)),
+    /* 0x3B */ op("DEC", &[SP], 1, 8, 8, "----", Some(I::DEC(IDT::SP))),
+    /* 0x3C */ op("INC", &[A], 1, 4, 4, "Z0H-", Some(I::INC(IDT::A))),
+    /* 0x3D */ op("DEC", &[A], 1, 4, 4, "Z1H-", Some(I::DEC(IDT::A))),
+    /* 0x3E */ op("LD", &[A, D8], 2, 8, 8, "----", ld(To::A, Src::D8)),
+    /* 0x3F */ op("CCF", &[], 1, 4, 4, "-00C", Some(I::CCF())),
+    /* 0x40 */ op("LD", &[B, B], 1, 4, 4, "----", ld(To::B, Src::B)),
+    /* 0x41 */ op("LD", &[B, C], 1, 4, 4, "----", ld(To::B, Src::C)),
+    /* 0x42 */ op("LD", &[B, D], 1, 4, 4, "----", ld(To::B, Src::D)),
+    /* 0x43 */ op("LD", &[B, E], 1, 4, 4, "----", ld(To::B, Src::E)),
+    /* 0x44 */ op("LD", &[B, H], 1, 4, 4, "----", ld(To::B, Src::H)),
+    /* 0x45 */ op("LD", &[B, L], 1, 4, 4, "----", ld(To::B, Src::L)),
+    /* 0x46 */ op("LD", &[B, IndHL], 1, 8, 8, "----", ld(To::B, Src::HL)),
+    /* 0x47 */ op("LD", &[B, A], 1, 4, 4, "----", ld(To::B, Src::A)),
+    /* 0x48 */ op("LD", &[C, B], 1, 4, 4, "----", ld(To::C, Src::B)),
+    /* 0x49 */ op("LD", &[C, C], 1, 4, 4, "----", ld(To::C, Src::C)),
+    /* 0x4A */ op("LD", &[C, D], 1, 4, 4, "----", ld(To::C, Src::D)),
+    /* 0x4B */ op("LD", &[C, E], 1, 4, 4, "----", ld(To::C, Src::E)),
+    /* 0x4C */ op("LD", &[C, H], 1, 4, 4, "----", ld(To::C, Src::H)),
+    /* 0x4D */ op("LD", &[C, L], 1, 4, 4, "----", ld(To::C, Src::L)),
+    /* 0x4E */ op("LD", &[C, IndHL], 1, 8, 8, "----", ld(To::C, Src::HL)),
+    /* 0x4F */ op("LD", &[C, A], 1, 4, 4, "----", ld(To::C, Src::A)),
+    /* 0x50 */ op("LD", &[D, B], 1, 4, 4, "----", ld(To::D, Src::B)),
+    /* 0x51 */ op("LD", &[D, C], 1, 4, 4, "----", ld(To::D, Src::C)),
+    /* 0x52 */ op("LD", &[D, D], 1, 4, 4, "----", ld(To::D, Src::D)),
+    /* 0x53 */ op("LD", &[D, E], 1, 4, 4, "----", ld(To::D, Src::E)),
+    /* 0x54 */ op("LD", &[D, H], 1, 4, 4, "----", ld(To::D, Src::H)),
+    /* 0x55 */ op("LD", &[D, L], 1, 4, 4, "----", ld(To::D, Src::L)),
+    /* 0x56 */ op("LD", &[D, IndHL], 1, 8, 8, "----", ld(To::D, Src::HL)),
+    /* 0x57 */ op("LD", &[D, A], 1, 4, 4, "----", ld(To::D, Src::A)),
+    /* 0x58 */ op("LD", &[E, B], 1, 4, 4, "----", ld(To::E, Src::B)),
+    /* 0x59 */ op("LD", &[E, C], 1, 4, 4, "----", ld(To::E, Src::C)),
+    /* 0x5A */ op("LD", &[E, D], 1, 4, 4, "----", ld(To::E, Src::D)),
+    /* 0x5B */ op("LD", &[E, E], 1, 4, 4, "----", ld(To::E, Src::E)),
+    /* 0x5C */ op("LD", &[E, H], 1, 4, 4, "----", ld(To::E, Src::H)),
+    /* 0x5D */ op("LD", &[E, L], 1, 4, 4, "----", ld(To::E, Src::L)),
+    /* 0x5E */ op("LD", &[E, IndHL], 1, 8, 8, "----", ld(To::E, Src::HL)),
+    /* 0x5F */ op("LD", &[E, A], 1, 4, 4, "----", ld(To::E, Src::A)),
+    /* 0x60 */ op("LD", &[H, B], 1, 4, 4, "----", ld(To::H, Src::B)),
+    /* 0x61 */ op("LD", &[H, C], 1, 4, 4, "----", ld(To::H, Src::C)),
+    /* 0x62 */ op("LD", &[H, D], 1, 4, 4, "----", ld(To::H, Src::D)),
+    /* 0x63 */ op("LD", &[H, E], 1, 4, 4, "----", ld(To::H, Src::E)),
+    /* 0x64 */ op("LD", &[H, H], 1, 4, 4, "----", ld(To::H, Src::H)),
+    /* 0x65 */ op("LD", &[H, L], 1, 4, 4, "----", ld(To::H, Src::L)),
+    /* 0x66 */ op("LD", &[H, IndHL], 1, 8, 8, "----", ld(To::H, Src::HL)),
+    /* 0x67 */ op("LD", &[H, A], 1, 4, 4, "----", ld(To::H, Src::A)),
+    /* 0x68 */ op("LD", &[L, B], 1, 4, 4, "----", ld(To::L, Src::B)),
+    /* 0x69 */ op("LD", &[L, C], 1, 4, 4, "----", ld(To::L, Src::C)),
+    /* 0x6A */ op("LD", &[L, D], 1, 4, 4, "----", ld(To::L, Src::D)),
+    /* 0x6B */ op("LD", &[L, E], 1, 4, 4, "----", ld(To::L, Src::E)),
+    /* 0x6C */ op("LD", &[L, H], 1, 4, 4, "----", ld(To::L, Src::H)),
+    /* 0x6D */ op("LD", &[L, L], 1, 4, 4, "----", ld(To::L, Src::L)),
+    /* 0x6E */ op("LD", &[L, IndHL], 1, 8, 8, "----", ld(To::L, Src::HL)),
+    /* 0x6F */ op("LD", &[L, A], 1, 4, 4, "----", ld(To::L, Src::A)),
+    /* 0x70 */ op("LD", &[IndHL, B], 1, 8, 8, "----", ld(To::HL, Src::B)),
+    /* 0x71 */ op("LD", &[IndHL, C], 1, 8, 8, "----", ld(To::HL, Src::C)),
+    /* 0x72 */ op("LD", &[IndHL, D], 1, 8, 8, "----", ld(To::HL, Src::D)),
+    /* 0x73 */ op("LD", &[IndHL, E], 1, 8, 8, "----", ld(To::HL, Src::E)),
+    /* 0x74 */ op("LD", &[IndHL, H], 1, 8, 8, "----", ld(To::HL, Src::H)),
+    /* 0x75 */ op("LD", &[IndHL, L], 1, 8, 8, "----", ld(To::HL, Src::L)),
+    /* 0x76 */ op("HALT", &[], 1, 4, 4, "----", Some(I::HALT())),
+    /* 0x77 */ op("LD", &[IndHL, A], 1, 8, 8, "----", ld(To::HL, Src::A)),
+    /* 0x78 */ op("LD", &[A, B], 1, 4, 4, "----", ld(To::A, Src::B)),
+    /* 0x79 */ op("LD", &[A, C], 1, 4, 4, "----", ld(To::A, Src::C)),
+    /* 0x7A */ op("LD", &[A, D], 1, 4, 4, "----", ld(To::A, Src::D)),
+    /* 0x7B */ op("LD", &[A, E], 1, 4, 4, "----", ld(To::A, Src::E)),
+    /* 0x7C */ op("LD", &[A, H], 1, 4, 4, "----", ld(To::A, Src::H)),
+    /* 0x7D */ op("LD", &[A, L], 1, 4, 4, "----", ld(To::A, Src::L)),
+    /* 0x7E */ op("LD", &[A, IndHL], 1, 8, 8, "----", ld(To::A, Src::HL)),
+    /* 0x7F */ op("LD", &[A, A], 1, 4, 4, "----", ld(To::A, Src::A)),
+    /* 0x80 */ op("ADD", &[A, B], 1, 4, 4, "Z0HC", Some(I::ADD(AT::B))),
+    /* 0x81 */ op("ADD", &[A, C], 1, 4, 4, "Z0HC", Some(I::ADD(AT::C))),
+    /* 0x82 */ op("ADD", &[A, D], 1, 4, 4, "Z0HC", Some(I::ADD(AT::D))),
+    /* 0x83 */ op("ADD", &[A, E], 1, 4, 4, "Z0HC", Some(I::ADD(AT::E))),
+    /* 0x84 */ op("ADD", &[A, H], 1, 4, 4, "Z0HC", Some(I::ADD(AT::H))),
+    /* 0x85 */ op("ADD", &[A, L], 1, 4, 4, "Z0HC", Some(I::ADD(AT::L))),
+    /* 0x86 */ op("ADD", &[A, IndHL], 1, 8, 8, "Z0HC", Some(I::ADD(AT::HL))),
+    /* 0x87 */ op("ADD", &[A, A], 1, 4, 4, "Z0HC", Some(I::ADD(AT::A))),
+    /* 0x88 */ op("ADC", &[A, B], 1, 4, 4, "Z0HC", Some(I::ADC(AT::B))),
+    /* 0x89 */ op("ADC", &[A, C], 1, 4, 4, "Z0HC", Some(I::ADC(AT::C))),
+    /* 0x8A */ op("ADC", &[A, D], 1, 4, 4, "Z0HC", Some(I::ADC(AT::D))),
+    /* 0x8B */ op("ADC", &[A, E], 1, 4, 4, "Z0HC", Some(I::ADC(AT::E))),
+    /* 0x8C */ op("ADC", &[A, H], 1, 4, 4, "Z0HC", Some(I::ADC(AT::H))),
+    /* 0x8D */ op("ADC", &[A, L], 1, 4, 4, "Z0HC", Some(I::ADC(AT::L))),
+    /* 0x8E */ op("ADC", &[A, IndHL], 1, 8, 8, "Z0HC", Some(I::ADC(AT::HL))),
+    /* 0x8F */ op("ADC", &[A, A], 1, 4, 4, "Z0HC", Some(I::ADC(AT::A))),
+    /* 0x90 */ op("SUB", &[B], 1, 4, 4, "Z1HC", Some(I::SUB(AT::B))),
+    /* 0x91 */ op("SUB", &[C], 1, 4, 4, "Z1HC", Some(I::SUB(AT::C))),
+    /* 0x92 */ op("SUB", &[D], 1, 4, 4, "Z1HC", Some(I::SUB(AT::D))),
+    /* 0x93 */ op("SUB", &[E], 1, 4, 4, "Z1HC", Some(I::SUB(AT::E))),
+    /* 0x94 */ op("SUB", &[H], 1, 4, 4, "Z1HC", Some(I::SUB(AT::H))),
+    /* 0x95 */ op("SUB", &[L], 1, 4, 4, "Z1HC", Some(I::SUB(AT::L))),
+    /* 0x96 */ op("SUB", &[IndHL], 1, 8, 8, "Z1HC", Some(I::SUB(AT::HL))),
+    /* 0x97 */ op("SUB", &[A], 1, 4, 4, "Z1HC", Some(I::SUB(AT::A))),
+    /* 0x98 */ op("SBC", &[A, B], 1, 4, 4, "Z1HC", Some(I::SBC(AT::B))),
+    /* 0x99 */ op("SBC", &[A, C], 1, 4, 4, "Z1HC", Some(I::SBC(AT::C))),
+    /* 0x9A */ op("SBC", &[A, D], 1, 4, 4, "Z1HC", Some(I::SBC(AT::D))),
+    /* 0x9B */ op("SBC", &[A, E], 1, 4, 4, "Z1HC", Some(I::SBC(AT::E))),
+    /* 0x9C */ op("SBC", &[A, H], 1, 4, 4, "Z1HC", Some(I::SBC(AT::H))),
+    /* 0x9D */ op("SBC", &[A, L], 1, 4, 4, "Z1HC", Some(I::SBC(AT::L))),
+    /* 0x9E */ op("SBC", &[A, IndHL], 1, 8, 8, "Z1HC", Some(I::SBC(AT::HL))),
+    /* 0x9F */ op("SBC", &[A, A], 1, 4, 4, "Z1HC", Some(I::SBC(AT::A))),
+    /* 0xA0 */ op("AND", &[B], 1, 4, 4, "Z010", Some(I::AND(AT::B))),
+    /* 0xA1 */ op("AND", &[C], 1, 4, 4, "Z010", Some(I::AND(AT::C))),
+    /* 0xA2 */ op("AND", &[D], 1, 4, 4, "Z010", Some(I::AND(AT::D))),
+    /* 0xA3 */ op("AND", &[E], 1, 4, 4, "Z010", Some(I::AND(AT::E))),
+    /* 0xA4 */ op("AND", &[H], 1, 4, 4, "Z010", Some(I::AND(AT::H))),
+    /* 0xA5 */ op("AND", &[L], 1, 4, 4, "Z010", Some(I::AND(AT::L))),
+    /* 0xA6 */ op("AND", &[IndHL], 1, 8, 8, "Z010", Some(I::AND(AT::HL))),
+    /* 0xA7 */ op("AND", &[A], 1, 4, 4, "Z010", Some(I::AND(AT::A))),
+    /* 0xA8 */ op("XOR", &[B], 1, 4, 4, "Z000", Some(I::XOR(AT::B))),
+    /* 0xA9 */ op("XOR", &[C], 1, 4, 4, "Z000", Some(I::XOR(AT::C))),
+    /* 0xAA */ op("XOR", &[D], 1, 4, 4, "Z000", Some(I::XOR(AT::D))),
+    /* 0xAB */ op("XOR", &[E], 1, 4, 4, "Z000", Some(I::XOR(AT::E))),
+    /* 0xAC */ op("XOR", &[H], 1, 4, 4, "Z000", Some(I::XOR(AT::H))),
+    /* 0xAD */ op("XOR", &[L], 1, 4, 4, "Z000", Some(I::XOR(AT::L))),
+    /* 0xAE */ op("XOR", &[IndHL], 1, 8, 8, "Z000", Some(I::XOR(AT::HL))),
+    /* 0xAF */ op("XOR", &[A], 1, 4, 4, "Z000", Some(I::XOR(AT::A))),
+    /* 0xB0 */ op("OR", &[B], 1, 4, 4, "Z000", Some(I::OR(AT::B))),
+    /* 0xB1 */ op("OR", &[C], 1, 4, 4, "Z000", Some(I::OR(AT::C))),
+    /* 0xB2 */ op("OR", &[D], 1, 4, 4, "Z000", Some(I::OR(AT::D))),
+    /* 0xB3 */ op("OR", &[E], 1, 4, 4, "Z000", Some(I::OR(AT::E))),
+    /* 0xB4 */ op("OR", &[H], 1, 4, 4, "Z000", Some(I::OR(AT::H))),
+    /* 0xB5 */ op("OR", &[L], 1, 4, 4, "Z000", Some(I::OR(AT::L))),
+    /* 0xB6 */ op("OR", &[IndHL], 1, 8, 8, "Z000", Some(I::OR(AT::HL))),
+    /* 0xB7 */ op("OR", &[A], 1, 4, 4, "Z000", Some(I::OR(AT::A))),
+    /* 0xB8 */ op("CP", &[B], 1, 4, 4, "Z1HC", Some(I::CP(AT::B))),
+    /* 0xB9 */ op("CP", &[C], 1, 4, 4, "Z1HC", Some(I::CP(AT::C))),
+    /* 0xBA */ op("CP", &[D], 1, 4, 4, "Z1HC", Some(I::CP(AT::D))),
+    /* 0xBB */ op("CP", &[E], 1, 4, 4, "Z1HC", Some(I::CP(AT::E))),
+    /* 0xBC */ op("CP", &[H], 1, 4, 4, "Z1HC", Some(I::CP(AT::H))),
+    /* 0xBD */ op("CP", &[L], 1, 4, 4, "Z1HC", Some(I::CP(AT::L))),
+    /* 0xBE */ op("CP", &[IndHL], 1, 8, 8, "Z1HC", Some(I::CP(AT::HL))),
+    /* 0xBF */ op("CP", &[A], 1, 4, 4, "Z1HC", Some(I::CP(AT::A))),
+    /* 0xC0 */ op("RET", &[CondNZ], 1, 8, 20, "----", Some(I::RET(JT::NotZero))),
+    /* 0xC1 */ op("POP", &[BC], 1, 12, 12, "----", Some(I::POP(ST::BC))),
+    /* 0xC2 */ op("JP", &[CondNZ, A16], 3, 12, 16, "----", Some(I::JP(JT::NotZero,JTG::A16))),
+    /* 0xC3 */ op("JP", &[A16], 3, 16, 16, "----", Some(I::JP(JT::Always,JTG::A16))),
+    /* 0xC4 */ op("CALL", &[CondNZ, A16], 3, 12, 24, "----", Some(I::CALL(JT::NotZero))),
+    /* 0xC5 */ op("PUSH", &[BC], 1, 16, 16, "----", Some(I::PUSH(ST::BC))),
+    /* 0xC6 */ op("ADD", &[A, D8], 2, 8, 8, "Z0HC", Some(I::ADD(AT::D8))),
+    /* 0xC7 */ op("RST", &[Rst(0x00)], 1, 16, 16, "----", Some(I::RST(RT::H00))),
+    /* 0xC8 */ op("RET", &[CondZ], 1, 8, 20, "----", Some(I::RET(JT::Zero))),
+    /* 0xC9 */ op("RET", &[], 1, 16, 16, "----", Some(I::RET(JT::Always))),
+    /* 0xCA */ op("JP", &[CondZ, A16], 3, 12, 16, "----", Some(I::JP(JT::Zero,JTG::A16))),
+    /* 0xCB */ op("PREFIX", &[], 1, 4, 4, "----", Some(I::PREFIX())),
+    /* 0xCC */ op("CALL", &[CondZ, A16], 3, 12, 24, "----", Some(I::CALL(JT::Zero))),
+    /* 0xCD */ op("CALL", &[A16], 3, 24, 24, "----", Some(I::CALL(JT::Always))),
+    /* 0xCE */ op("ADC", &[A, D8], 2, 8, 8, "Z0HC", Some(I::ADC(AT::D8))),
+    /* 0xCF */ op("RST", &[Rst(0x08)], 1, 16, 16, "----", Some(I::RST(RT::H08))),
+    /* 0xD0 */ op("RET", &[CondNC], 1, 8, 20, "----", Some(I::RET(JT::NotCarry))),
+    /* 0xD1 */ op("POP", &[DE], 1, 12, 12, "----", Some(I::POP(ST::DE))),
+    /* 0xD2 */ op("JP", &[CondNC, A16], 3, 12, 16, "----", Some(I::JP(JT::NotCarry,JTG::A16))),
+    /* 0xD3 */ INVALID,
+    /* 0xD4 */ op("CALL", &[CondNC, A16], 3, 12, 24, "----", Some(I::CALL(JT::NotCarry))),
+    /* 0xD5 */ op("PUSH", &[DE], 1, 16, 16, "----", Some(I::PUSH(ST::DE))),
+    /* 0xD6 */ op("SUB", &[D8], 2, 8, 8, "Z1HC", Some(I::SUB(AT::D8))),
+    /* 0xD7 */ op("RST", &[Rst(0x10)], 1, 16, 16, "----", Some(I::RST(RT::H10))),
+    /* 0xD8 */ op("RET", &[CondC], 1, 8, 20, "----", Some(I::RET(JT::Carry))),
+    /* 0xD9 */ op("RETI", &[], 1, 16, 16, "----", Some(I::RETI())),
+    /* 0xDA */ op("JP", &[CondC, A16], 3, 12, 16, "----", Some(I::JP(JT::Carry,JTG::A16))),
+    /* 0xDB */ INVALID,
+    /* 0xDC */ op("CALL", &[CondC, A16], 3, 12, 24, "----", Some(I::CALL(JT::Carry))),
+    /* 0xDD */ INVALID,
+    /* 0xDE */ op("SBC", &[A, D8], 2, 8, 8, "Z1HC", Some(I::SBC(AT::D8))),
+    /* 0xDF */ op("RST", &[Rst(0x18)], 1, 16, 16, "----", Some(I::RST(RT::H18))),
+    /* 0xE0 */ op("LDH", &[A8, A], 2, 12, 12, "----", ld(To::A8, Src::A)),
+    /* 0xE1 */ op("POP", &[HL], 1, 12, 12, "----", Some(I::POP(ST::HL))),
+    /* 0xE2 */ op("LD", &[IndC, A], 1, 8, 8, "----", ld(To::FF00C, Src::A)),
+    /* 0xE3 */ INVALID,
+    /* 0xE4 */ INVALID,
+    /* 0xE5 */ op("PUSH", &[HL], 1, 16, 16, "----", Some(I::PUSH(ST::HL))),
+    /* 0xE6 */ op("AND", &[D8], 2, 8, 8, "Z010", Some(I::AND(AT::D8))),
+    /* 0xE7 */ op("RST", &[Rst(0x20)], 1, 16, 16, "----", Some(I::RST(RT::H20))),
+    /* 0xE8 */ op("ADD", &[SP, R8], 2, 16, 16, "00HC", Some(I::ADD(AT::SP))),
+    /* 0xE9 */ op("JP", &[IndHL], 1, 4, 4, "----", Some(I::JP(JT::Always,JTG::HL))),
+    /* 0xEA */ op("LD", &[IndA16, A], 3, 16, 16, "----", ld(To::A16, Src::A)),
+    /* 0xEB */ INVALID,
+    /* 0xEC */ INVALID,
+    /* 0xED */ INVALID,
+    /* 0xEE */ op("XOR", &[D8], 2, 8, 8, "Z000", Some(I::XOR(AT::D8))),
+    /* 0xEF */ op("RST", &[Rst(0x28)], 1, 16, 16, "----", Some(I::RST(RT::H28))),
+    /* 0xF0 */ op("LDH", &[A, A8], 2, 12, 12, "----", ld(To::A, Src::A8)),
+    /* 0xF1 */ op("POP", &[AF], 1, 12, 12, "ZNHC", Some(I::POP(ST::AF))),
+    /* 0xF2 */ op("LD", &[A, IndC], 1, 8, 8, "----", ld(To::A, Src::FF00C)),
+    /* 0xF3 */ op("DI", &[], 1, 4, 4, "----", Some(I::DI())),
+    /* 0xF4 */ INVALID,
+    /* 0xF5 */ op("PUSH", &[AF], 1, 16, 16, "----", Some(I::PUSH(ST::AF))),
+    /* 0xF6 */ op("OR", &[D8], 2, 8, 8, "Z000", Some(I::OR(AT::D8))),
+    /* 0xF7 */ op("RST", &[Rst(0x30)], 1, 16, 16, "----", Some(I::RST(RT::H30))),
+    /* 0xF8 */ op("LD", &[HL, SPR8], 2, 12, 12, "00HC", ld(To::HL, Src::SP)),
+    /* 0xF9 */ op("LD", &[SP, HL], 1, 8, 8, "----", ld(To::SP, Src::HL)),
+    /* 0xFA */ op("LD", &[A, IndA16], 3, 16, 16, "----", ld(To::A, Src::A16)),
+    /* 0xFB */ op("EI", &[], 1, 4, 4, "----", Some(I::EI())),
+    /* 0xFC */ INVALID,
+    /* 0xFD */ INVALID,
+    /* 0xFE */ op("CP", &[D8], 2, 8, 8, "Z1HC", Some(I::CP(AT::D8))),
+    /* 0xFF */ op("RST", &[Rst(0x38)], 1, 16, 16, "----", Some(I::RST(RT::H38))),
+];
+
+static CB_OPCODES: [OpcodeInfo; 256] = cb_opcodes();
+
+//The 0xCB page is regular: operation in bits 3-7, register in bits 0-2
+const fn cb_opcodes() -> [OpcodeInfo; 256] {
+    const REGISTERS: [(Operand, PrefixTarget); 8] = [
+        (B, PrefixTarget::B),
+        (C, PrefixTarget::C),
+        (D, PrefixTarget::D),
+        (E, PrefixTarget::E),
+        (H, PrefixTarget::H),
+        (L, PrefixTarget::L),
+        (IndHL, PrefixTarget::HL),
+        (A, PrefixTarget::A),
+    ];
+    let mut table = [INVALID; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let (operand, target) = REGISTERS[byte & 0x07];
+        let hl = byte & 0x07 == 6;
+        let bit = ((byte >> 3) & 0x07) as u8;
+        let (mnemonic, flags, instruction) = match byte >> 3 {
+            0 => ("RLC", "Z00C", I::RLC(target)),
+            1 => ("RRC", "Z00C", I::RRC(target)),
+            2 => ("RL", "Z00C", I::RL(target)),
+            3 => ("RR", "Z00C", I::RR(target)),
+            4 => ("SLA", "Z00C", I::SLA(target)),
+            5 => ("SRA", "Z00C", I::SRA(target)),
+            6 => ("SWAP", "Z000", I::SWAP(target)),
+            7 => ("SRL", "Z00C", I::SRL(target)),
+            8..=15 => ("BIT", "Z01-", I::BIT(bit, target)),
+            16..=23 => ("RES", "----", I::RES(bit, target)),
+            _ => ("SET", "----", I::SET(bit, target)),
+        };
+        //BIT only reads (HL), the others read and write it back
+        let cycles = match (hl, byte >> 6) {
+            (false, _) => 8,
+            (true, 1) => 12,
+            (true, _) => 16,
+        };
+        table[byte] = if byte >> 6 == 0 {
+            op(mnemonic, &[operand], 2, cycles, cycles, flags, Some(instruction))
+        } else {
+            op(mnemonic, &[Bit(bit), operand], 2, cycles, cycles, flags, Some(instruction))
+        };
+        byte += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths_match_operands() {
+        for prefixed in [false, true] {
+            for byte in 0..=255u8 {
+                let info = OpcodeInfo::get(byte, prefixed);
+                if info.instruction.is_none() || matches!(info.instruction, Some(Instruction::STOP())) {
+                    continue;
+                }
+                let expected = 1 + prefixed as u8 + info.operands().map(|o| o.immediate_size()).sum::<u8>();
+                assert_eq!(info.length, expected, "{} ({:#04x}, prefixed: {})", info, byte, prefixed);
+            }
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn only_conditional_branches_have_two_timings() {
+        for byte in 0..=255u8 {
+            let info = OpcodeInfo::get(byte, false);
+            let conditional = info.operands().any(|o| matches!(o, CondNZ | CondZ | CondNC | CondC));
+            assert_eq!(info.is_conditional(), conditional, "{}", info);
+        }
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(OpcodeInfo::get(0xFA, false).to_string(), "LD A,(a16)");
+        assert_eq!(OpcodeInfo::get(0xE0, false).to_string(), "LDH (a8),A");
+        assert_eq!(OpcodeInfo::get(0x20, false).to_string(), "JR NZ,r8");
+        assert_eq!(OpcodeInfo::get(0xFF, false).to_string(), "RST 38H");
+        assert_eq!(OpcodeInfo::get(0x7E, true).to_string(), "BIT 7,(HL)");
+        assert_eq!(OpcodeInfo::get(0xAE, false).instruction, Some(Instruction::XOR(ArithmeticTarget::HL)));
+        assert_eq!(OpcodeInfo::get(0x27, false).flags.to_string(), "Z-0C");
+    }
+
+    #[test]
+    fn cb_cycles() {
+        assert_eq!(OpcodeInfo::get(0x00, true).cycles, 8);
+        assert_eq!(OpcodeInfo::get(0x06, true).cycles, 16);
+        assert_eq!(OpcodeInfo::get(0x46, true).cycles, 12);
+        assert_eq!(OpcodeInfo::get(0xC6, true).cycles, 16);
+        assert!(OpcodeInfo::get(0xD3, false).instruction.is_none());
+    }
+}
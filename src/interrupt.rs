@@ -0,0 +1,94 @@
+//Interrupt sources, in priority order (bit in IE and IF)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    //Handler address, 0x40 to 0x60
+    pub fn vector(self) -> u16 {
+        0x0040 + 8 * self as u16
+    }
+}
+
+//IE (0xFFFF) and IF (0xFF0F). Devices raise their line by setting the IF bit,
+//the game can clear or set any of them by writing IF.
+#[derive(Default)]
+pub struct InterruptController {
+    pub enable: u8,
+    flags: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> InterruptController {
+        InterruptController::default()
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flags |= interrupt.bit();
+    }
+
+    //Clear the request when the CPU jumps to the handler
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flags &= !interrupt.bit();
+    }
+
+    //Only 5 bits are wired, the upper 3 always read as 1
+    pub fn read_if(&self) -> u8 {
+        0xE0 | self.flags
+    }
+
+    pub fn write_if(&mut self, val: u8) {
+        self.flags = val & 0x1F;
+    }
+
+    //Highest priority interrupt both requested and enabled
+    pub fn pending(&self) -> Option<Interrupt> {
+        let pending = self.enable & self.flags;
+        Interrupt::ALL.into_iter().find(|i| pending & i.bit() != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_upper_bits_read_as_one() {
+        let mut interrupts = InterruptController::new();
+        assert_eq!(interrupts.read_if(), 0xE0);
+        interrupts.write_if(0xFF);
+        assert_eq!(interrupts.read_if(), 0xFF);
+        interrupts.write_if(0x00);
+        assert_eq!(interrupts.read_if(), 0xE0);
+    }
+
+    #[test]
+    fn game_writes_clear_requests() {
+        let mut interrupts = InterruptController::new();
+        interrupts.enable = 0x1F;
+        interrupts.request(Interrupt::Timer);
+        interrupts.request(Interrupt::Joypad);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Timer));
+        interrupts.write_if(interrupts.read_if() & !Interrupt::Timer.bit());
+        assert_eq!(interrupts.pending(), Some(Interrupt::Joypad));
+        interrupts.write_if(0);
+        assert_eq!(interrupts.pending(), None);
+    }
+}
@@ -0,0 +1,61 @@
+//State of the 8 buttons, true when pressed
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Buttons {
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool,
+    pub right: bool,
+    pub left: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+//P1/JOYP register (0xFF00)
+pub struct Joypad {
+    //Bit 4 low selects the d-pad, bit 5 low selects the buttons
+    select: u8,
+    buttons: Buttons,
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad {
+            select: 0x30,
+            buttons: Buttons::default(),
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & 0x10 == 0 {
+            lines &= !Joypad::pack(self.buttons.right, self.buttons.left, self.buttons.up, self.buttons.down);
+        }
+        if self.select & 0x20 == 0 {
+            lines &= !Joypad::pack(self.buttons.a, self.buttons.b, self.buttons.select, self.buttons.start);
+        }
+        0xC0 | self.select | lines
+    }
+
+    pub fn write(&mut self, val: u8) {
+        self.select = val & 0x30;
+    }
+
+    //Returns true when a button was pressed, which requests the joypad interrupt
+    pub fn set_buttons(&mut self, buttons: Buttons) -> bool {
+        let before = self.read();
+        self.buttons = buttons;
+        //A selected line going from high to low
+        before & !self.read() & 0x0F != 0
+    }
+
+    fn pack(bit0: bool, bit1: bool, bit2: bool, bit3: bool) -> u8 {
+        (bit0 as u8) | (bit1 as u8) << 1 | (bit2 as u8) << 2 | (bit3 as u8) << 3
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Joypad {
+        Joypad::new()
+    }
+}
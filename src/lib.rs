@@ -0,0 +1,24 @@
+#![allow(clippy::upper_case_acronyms)]
+pub mod asm;
+pub mod cartridge;
+pub mod cpu;
+pub mod disasm;
+pub mod emulator;
+pub mod headless;
+pub mod instruction;
+pub mod interrupt;
+pub mod joypad;
+pub mod launch;
+pub mod mbc;
+pub mod ppu;
+pub mod register;
+pub mod save;
+pub mod scheduler;
+pub mod screenshot;
+pub mod serial;
+pub mod tile;
+pub mod timer;
+pub mod tracediff;
+
+pub use emulator::Emulator;
+pub use joypad::Buttons;
@@ -1,58 +1,136 @@
-mod register;
-mod cpu;
-mod instruction;
-mod timer;
-mod launch;
-mod ppu;
-mod tile;
+mod cli;
 mod gpu;
-use std::thread;
-use std::sync::mpsc;
-use crate::mpsc::TryRecvError;
-
-fn main() {     
-
-    let (tx1, rx1) = mpsc::channel();
-    let (tx2, rx2) = mpsc::channel();
-    let (tx3, rx3) = mpsc::channel();
-
-    thread::spawn(move || {
-        let mut screen = gpu::Screen::new();
-        screen.render_screen(&rx1, &tx2, &tx3);
-
-    });
-
-    
-
-    let mut cpu = cpu::CPU::new();
-    let mut i = 0;
-    loop{
-        match rx2.try_recv() {
-            Ok(data) => {cpu.bus.joypad = data.buttons},
-            Err(TryRecvError::Disconnected) => {/* handle sender disconnected */}
-            Err(TryRecvError::Empty) => {/* handle no data available yet */}
-        }
-        match rx3.try_recv() {
-            Ok(data) => {cpu.jpad_interrupt = data},
-            Err(TryRecvError::Disconnected) => {/* handle sender disconnected */}
-            Err(TryRecvError::Empty) => {/* handle no data available yet */}
-        }
-        let _ = cpu.interrupts();
-        print!("{} ",i+1);
-        cpu.step();
-        if cpu.bus.timer.cycles_counter >= 456 {
-            if cpu.bus.ppu.ly == 144{
-                let _= tx1.send(cpu.bus.ppu.video_buffer);
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::process;
+use log::{error, info, warn};
+use emulator::mbc::RtcClock;
+use emulator::headless::{HeadlessRunner, InputScript};
+use emulator::tracediff::{self, Outcome};
+use emulator::{disasm, launch, Emulator};
+
+const BOOT_ROM_SIZE: usize = 0x100;
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn disassemble(options: &cli::DisasmOptions) {
+    let rom = fs::read(&options.rom_path)
+        .unwrap_or_else(|e| fail(format!("Couldn't read {}: {}", options.rom_path.display(), e)));
+    let read = |address: u16| rom.get(address as usize).copied().unwrap_or(0xFF);
+    if options.recursive {
+        let mut entries = disasm::ENTRY_POINTS.to_vec();
+        entries.extend(options.from);
+        let end = rom.len().min(0x8000) as u16;
+        let mut next = None;
+        for line in disasm::recursive(read, &entries, end).values().take(options.count.unwrap_or(usize::MAX)) {
+            //Blank line between blocks that aren't contiguous
+            if next.is_some_and(|next| next != line.address) {
+                println!();
             }
-            cpu.bus.ppu.ppu_step();
+            println!("{}", line);
+            next = Some(line.next_address());
+        }
+    } else {
+        for line in disasm::linear(read, options.from.unwrap_or(0x0100), options.count.unwrap_or(100)) {
+            println!("{}", line);
+        }
+    }
+}
+
+fn trace_diff(options: &cli::TraceDiffOptions) {
+    let cartridge = launch::launch(&options.rom_path)
+        .unwrap_or_else(|e| fail(format!("Couldn't load {}: {}", options.rom_path.display(), e)));
+    //The reference was recorded once, the clock must not depend on when we run
+    let mut emulator = Emulator::new(cartridge, RtcClock::Cycles)
+        .unwrap_or_else(|e| fail(format!("Couldn't load {}: {}", options.rom_path.display(), e)));
+    emulator.cpu.bus.ly_stub = options.ly_stub;
+    let reference = File::open(&options.reference)
+        .unwrap_or_else(|e| fail(format!("Couldn't read {}: {}", options.reference.display(), e)));
+    match tracediff::compare(&mut emulator, BufReader::new(reference), options.context) {
+        Ok(Outcome::Matched(count)) => println!("All {} instructions match the reference", count),
+        Ok(Outcome::Diverged(divergence)) => {
+            print!("{}", divergence);
+            process::exit(1);
+        }
+        Err(e) => fail(format!("Couldn't read {}: {}", options.reference.display(), e)),
+    }
+}
+
+fn main() {
+    //Silent except for warnings, RUST_LOG=cpu=trace,ppu=debug enables a subsystem
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli::Command::Run(options)) => options,
+        Ok(cli::Command::Disasm(options)) => {
+            disassemble(&options);
+            return;
+        }
+        Ok(cli::Command::TraceDiff(options)) => {
+            trace_diff(&options);
+            return;
+        }
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-            
-            cpu.bus.timer.cycles_counter %= 456;
+    let rom_name = options.rom_path.display();
+    let cartridge = launch::launch(&options.rom_path)
+        .unwrap_or_else(|e| fail(format!("Couldn't load {}: {}", rom_name, e)));
+    info!(target: "bus", "{}", cartridge.header);
+    let mut emulator = Emulator::new(cartridge, options.rtc)
+        .unwrap_or_else(|e| fail(format!("Couldn't load {}: {}", rom_name, e)));
+
+    emulator.save_dir = options.save_dir.clone();
+    if let Err(e) = emulator.attach_battery(&options.rom_path) {
+        warn!(target: "bus", "Couldn't load the save file: {}", e);
+    }
+    if let Some(path) = &options.boot_rom {
+        let boot_rom = fs::read(path)
+            .unwrap_or_else(|e| fail(format!("Couldn't read boot ROM {}: {}", path.display(), e)));
+        if boot_rom.len() != BOOT_ROM_SIZE {
+            fail(format!("Boot ROM must be {} bytes, {} is {} bytes", BOOT_ROM_SIZE, path.display(), boot_rom.len()));
         }
-        println!(" ly: {}",cpu.bus.ppu.ly);
+        emulator.cpu.load_boot_rom(boot_rom);
+    }
+    if let Some(path) = &options.trace {
+        let file = File::create(path)
+            .unwrap_or_else(|e| fail(format!("Couldn't create trace file {}: {}", path.display(), e)));
+        emulator.cpu.set_trace(file);
+        //gameboy-doctor logs are recorded with LY stuck at 0x90
+        emulator.cpu.bus.ly_stub = options.ly_stub;
+    }
+
+    if options.headless {
+        let mut runner = HeadlessRunner::new();
+        if let Some(path) = &options.input {
+            let script = fs::read_to_string(path)
+                .unwrap_or_else(|e| fail(format!("Couldn't read input script {}: {}", path.display(), e)));
+            runner.input = InputScript::parse(&script)
+                .unwrap_or_else(|e| fail(format!("Invalid input script {}: {}", path.display(), e)));
+        }
+        //Without a frame count, stop once the last screenshot is written
+        runner.frames = options.frames.map(|n| n as u64).or(options.dumps.iter().map(|&(f, _)| f).max());
+        runner.dumps = options.dumps.clone();
+        runner.palette = Some(options.palette.0);
+        if let Err(e) = runner.run(&mut emulator) {
+            fail(format!("Couldn't write screenshot: {}", e));
+        }
+    } else {
+        let title = emulator.cpu.bus.header.title.clone();
+        let mut screen = gpu::Screen::new(&title, options.scale, options.palette);
+        screen.run(&mut emulator, options.frames);
+    }
 
-        println!(" Cycles : {}",cpu.bus.timer.cycles_counter);
-        i+=1;
-       
+    if let Err(e) = emulator.save() {
+        error!(target: "bus", "Couldn't write the save file: {}", e);
     }
-}
\ No newline at end of file
+}
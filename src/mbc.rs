@@ -0,0 +1,118 @@
+use crate::cartridge::{Cartridge, CartridgeError, MbcKind};
+
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rtc;
+
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use rtc::{RtcClock, RTC_SAVE_SIZE, RTC_SAVE_SIZE_LEGACY};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+//Cartridge hardware sitting behind 0x0000-0x7FFF and 0xA000-0xBFFF
+pub trait MemoryBankController {
+    fn read_rom(&self, address: u16) -> u8;
+    //Writes to the ROM area program the controller registers
+    fn write_rom(&mut self, address: u16, val: u8);
+    fn read_ram(&self, address: u16) -> u8;
+    //False when the write was dropped, RAM disabled or not present
+    fn write_ram(&mut self, address: u16, val: u8) -> bool;
+    //External RAM contents, as stored in the battery save
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+    fn load_ram(&mut self, data: &[u8]) {
+        let ram = self.ram_mut();
+        let len = data.len().min(ram.len());
+        ram[..len].copy_from_slice(&data[..len]);
+    }
+    //Called with the elapsed cycles, for controllers with their own clock
+    fn tick(&mut self, _cycles: u32) {}
+    //Real-time clock state saved after the RAM contents in the battery save
+    fn save_rtc(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+    fn load_rtc(&mut self, _data: &[u8]) {}
+    //True while the cartridge asks for the rumble motor to spin
+    fn rumble(&self) -> bool {
+        false
+    }
+}
+
+pub fn from_cartridge(cartridge: Cartridge, clock: RtcClock) -> Result<Box<dyn MemoryBankController>, CartridgeError> {
+    let ram_size = cartridge.header.ram_size;
+    let cartridge_type = cartridge.header.cartridge_type;
+    match cartridge_type.mbc {
+        MbcKind::None => Ok(Box::new(NoMbc::new(cartridge.rom, ram_size))),
+        MbcKind::Mbc1 => Ok(Box::new(Mbc1::new(cartridge.rom, ram_size))),
+        MbcKind::Mbc2 => Ok(Box::new(Mbc2::new(cartridge.rom))),
+        MbcKind::Mbc3 => Ok(Box::new(Mbc3::new(cartridge.rom, ram_size, cartridge_type.timer, clock))),
+        MbcKind::Mbc5 => Ok(Box::new(Mbc5::new(cartridge.rom, ram_size, cartridge_type.rumble))),
+        kind => Err(CartridgeError::UnsupportedMbc(kind)),
+    }
+}
+
+//32 KB ROM mapped directly, with optional 8 KB of RAM
+pub struct NoMbc {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl NoMbc {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> NoMbc {
+        NoMbc {
+            rom,
+            ram: vec![0; ram_size.min(RAM_BANK_SIZE)],
+        }
+    }
+}
+
+impl MemoryBankController for NoMbc {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _val: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram.get((address & 0x1FFF) as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, val: u8) -> bool {
+        match self.ram.get_mut((address & 0x1FFF) as usize) {
+            Some(byte) => {
+                *byte = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::ROM_BANK_SIZE;
+
+    //Each bank starts with its own number (low byte, high byte)
+    pub fn banked_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        rom
+    }
+}
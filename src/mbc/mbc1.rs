@@ -0,0 +1,209 @@
+use super::{MemoryBankController, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+//Nintendo logo location in the header, used to detect MBC1M multicarts
+const LOGO_START: usize = 0x0104;
+const LOGO_END: usize = 0x0134;
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    //5 bit register written at 0x2000-0x3FFF
+    bank1: u8,
+    //2 bit register written at 0x4000-0x5FFF
+    bank2: u8,
+    //Banking mode written at 0x6000-0x7FFF
+    mode: u8,
+    //MBC1M multicarts only wire 4 bits of BANK1, BANK2 selects the game
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Mbc1 {
+        let multicart = is_multicart(&rom);
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: 0,
+            multicart,
+        }
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        (self.rom.len() / ROM_BANK_SIZE).max(1)
+    }
+
+    fn rom_byte(&self, bank: usize, address: u16) -> u8 {
+        let bank = bank % self.rom_bank_count();
+        let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let bank = if self.mode == 1 { self.bank2 as usize } else { 0 };
+        let offset = bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+        Some(offset % self.ram.len())
+    }
+}
+
+impl MemoryBankController for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => {
+                //In mode 1 BANK2 also switches the first ROM area
+                let bank = if self.mode == 1 { (self.bank2 << self.bank2_shift()) as usize } else { 0 };
+                self.rom_byte(bank, address)
+            }
+            _ => {
+                let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+                let bank = ((self.bank2 << self.bank2_shift()) | bank1) as usize;
+                self.rom_byte(bank, address)
+            }
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, val: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                //Bank 0 can't be selected here, the zero check covers all 5 bits
+                self.bank1 = val & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = val & 0x03,
+            0x6000..=0x7FFF => self.mode = val & 0x01,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.ram_offset(address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, val: u8) -> bool {
+        match self.ram_offset(address) {
+            Some(offset) => {
+                self.ram[offset] = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+//MBC1M carts are 1 MB and hold a second copy of the header logo at the start of each 256 KB game
+fn is_multicart(rom: &[u8]) -> bool {
+    if rom.len() != 64 * ROM_BANK_SIZE {
+        return false;
+    }
+    let logo = &rom[LOGO_START..LOGO_END];
+    let second_game = 0x10 * ROM_BANK_SIZE;
+    &rom[second_game + LOGO_START..second_game + LOGO_END] == logo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::tests::banked_rom;
+
+    #[test]
+    fn bank1_zero_selects_bank_one() {
+        let mut mbc = Mbc1::new(banked_rom(32), 0);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        //Only the 5 register bits are checked for zero
+        mbc.write_rom(0x2000, 0xE0);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x1F);
+        assert_eq!(mbc.read_rom(0x4000), 0x1F);
+    }
+
+    #[test]
+    fn banks_0x20_0x40_0x60_map_one_higher() {
+        let mut mbc = Mbc1::new(banked_rom(128), 0);
+        mbc.write_rom(0x2000, 0x00);
+        for bank2 in 1..4 {
+            mbc.write_rom(0x4000, bank2);
+            assert_eq!(mbc.read_rom(0x4000), bank2 << 5 | 1);
+        }
+    }
+
+    #[test]
+    fn mode_1_banks_the_first_rom_area_and_ram() {
+        let mut mbc = Mbc1::new(banked_rom(128), 4 * RAM_BANK_SIZE);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x02);
+        //Mode 0, BANK2 only applies to 0x4000-0x7FFF and RAM stays on bank 0
+        assert_eq!(mbc.read_rom(0x0000), 0);
+        mbc.write_ram(0xA000, 0x11);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x40);
+        assert_eq!(mbc.read_rom(0x4000), 0x41);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_ram(0xA000, 0x22);
+        mbc.write_rom(0x6000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+        assert_eq!(mbc.ram()[2 * RAM_BANK_SIZE], 0x22);
+    }
+
+    #[test]
+    fn ram_is_disabled_until_enabled() {
+        let mut mbc = Mbc1::new(banked_rom(4), RAM_BANK_SIZE);
+        assert!(!mbc.write_ram(0xA000, 0x42));
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        mbc.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        assert!(mbc.write_ram(0xA000, 0x42));
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn multicart_wires_4_bits_of_bank1() {
+        let mut rom = banked_rom(64);
+        for game in [0x00, 0x10, 0x20, 0x30] {
+            let start = game * ROM_BANK_SIZE;
+            rom[start + LOGO_START..start + LOGO_END].fill(0xCE);
+        }
+        let mut mbc = Mbc1::new(rom, 0);
+        assert!(mbc.multicart);
+        //BANK1 bit 4 is not connected, BANK2 lands on bits 4-5
+        mbc.write_rom(0x2000, 0x12);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x10);
+        mbc.write_rom(0x4000, 0x03);
+        assert_eq!(mbc.read_rom(0x0000), 0x30);
+    }
+
+    #[test]
+    fn plain_1mb_rom_is_not_a_multicart() {
+        let mut rom = banked_rom(64);
+        rom[LOGO_START..LOGO_END].fill(0xCE);
+        let mbc = Mbc1::new(rom, 0);
+        assert!(!mbc.multicart);
+    }
+}
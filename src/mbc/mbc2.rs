@@ -0,0 +1,134 @@
+use super::{MemoryBankController, ROM_BANK_SIZE};
+
+//512 half-bytes of RAM built into the controller
+const MBC2_RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: [u8; MBC2_RAM_SIZE],
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Mbc2 {
+        Mbc2 {
+            rom,
+            ram: [0; MBC2_RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl MemoryBankController for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        let bank = bank % (self.rom.len() / ROM_BANK_SIZE).max(1);
+        let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, address: u16, val: u8) {
+        //Both registers live in 0x0000-0x3FFF, address bit 8 picks which one is written
+        if address > 0x3FFF {
+            return;
+        }
+        if address & 0x0100 == 0 {
+            self.ram_enabled = val & 0x0F == 0x0A;
+        } else {
+            self.rom_bank = val & 0x0F;
+            if self.rom_bank == 0 {
+                self.rom_bank = 1;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        //Only the low nibble exists, the upper bits read as 1
+        self.ram[address as usize & (MBC2_RAM_SIZE - 1)] | 0xF0
+    }
+
+    fn write_ram(&mut self, address: u16, val: u8) -> bool {
+        if self.ram_enabled {
+            self.ram[address as usize & (MBC2_RAM_SIZE - 1)] = val & 0x0F;
+        }
+        self.ram_enabled
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::tests::banked_rom;
+
+    #[test]
+    fn address_bit_8_selects_rom_bank_register() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        mbc.write_rom(0x0100, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        mbc.write_rom(0x3FFF, 0x07);
+        assert_eq!(mbc.read_rom(0x4000), 7);
+        //Bit 8 clear writes the RAM enable register instead
+        mbc.write_rom(0x0000, 0x03);
+        mbc.write_rom(0x3EFF, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 7);
+    }
+
+    #[test]
+    fn address_bit_8_clear_selects_ram_enable() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        mbc.write_rom(0x0100, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        assert_eq!(mbc.read_rom(0x4000), 10);
+        mbc.write_rom(0x2000, 0x0A);
+        mbc.write_ram(0xA000, 0x03);
+        assert_eq!(mbc.read_ram(0xA000), 0xF3);
+        mbc.write_rom(0x00FF, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn rom_bank_is_four_bits_and_never_zero() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        mbc.write_rom(0x2100, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2100, 0x13);
+        assert_eq!(mbc.read_rom(0x4000), 3);
+        mbc.write_rom(0x2100, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn writes_above_0x3fff_are_ignored() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        mbc.write_rom(0x4100, 0x05);
+        mbc.write_rom(0x6000, 0x0A);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn ram_stores_nibbles_and_echoes() {
+        let mut mbc = Mbc2::new(banked_rom(2));
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA001, 0xAB);
+        assert_eq!(mbc.read_ram(0xA001), 0xFB);
+        assert_eq!(mbc.read_ram(0xA201), 0xFB);
+        assert_eq!(mbc.read_ram(0xBE01), 0xFB);
+    }
+}
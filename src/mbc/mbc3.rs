@@ -0,0 +1,197 @@
+use super::rtc::{Rtc, RtcClock};
+use super::{MemoryBankController, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    //Enables both the RAM and the RTC registers
+    ram_enabled: bool,
+    rom_bank: u8,
+    //0x00-0x03 select a RAM bank, 0x08-0x0C an RTC register
+    ram_select: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, timer: bool, clock: RtcClock) -> Mbc3 {
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+            rtc: if timer { Some(Rtc::new(clock)) } else { None },
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let offset = self.ram_select as usize * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+        Some(offset % self.ram.len())
+    }
+}
+
+impl MemoryBankController for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        let bank = bank % (self.rom.len() / ROM_BANK_SIZE).max(1);
+        let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, address: u16, val: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = val & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_select = val,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(val);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.ram_select {
+            0x00..=0x07 => match self.ram_offset(address) {
+                Some(offset) => self.ram[offset],
+                None => 0xFF,
+            },
+            0x08..=0x0C => match &self.rtc {
+                Some(rtc) if self.ram_enabled => rtc.read(self.ram_select),
+                _ => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, val: u8) -> bool {
+        match self.ram_select {
+            0x00..=0x07 => match self.ram_offset(address) {
+                Some(offset) => {
+                    self.ram[offset] = val;
+                    true
+                }
+                None => false,
+            },
+            //The clock is saved along with the RAM
+            0x08..=0x0C => match self.rtc.as_mut().filter(|_| self.ram_enabled) {
+                Some(rtc) => {
+                    rtc.write(self.ram_select, val);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.tick(cycles);
+        }
+    }
+
+    fn save_rtc(&mut self) -> Option<Vec<u8>> {
+        self.rtc.as_mut().map(|rtc| rtc.save_state())
+    }
+
+    fn load_rtc(&mut self, data: &[u8]) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load_state(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::tests::banked_rom;
+    use crate::mbc::RTC_SAVE_SIZE;
+
+
+    fn mbc3_with_timer() -> Mbc3 {
+        let mut mbc = Mbc3::new(banked_rom(128), 4 * RAM_BANK_SIZE, true, RtcClock::Cycles);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc
+    }
+
+    #[test]
+    fn rom_bank_is_seven_bits_and_never_zero() {
+        let mut mbc = mbc3_with_timer();
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(0x4000), 0x7F);
+        mbc.write_rom(0x2000, 0x80);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn ram_select_switches_between_ram_banks_and_rtc() {
+        let mut mbc = mbc3_with_timer();
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(0xA000, 0x55);
+        //Seconds register, visible after a latch
+        mbc.write_rom(0x4000, 0x08);
+        mbc.write_ram(0xA000, 30);
+        assert_eq!(mbc.read_ram(0xA000), 0);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 30);
+        assert_eq!(mbc.read_ram(0xBFFF), 30);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA000), 0x55);
+        assert_eq!(mbc.ram()[2 * RAM_BANK_SIZE], 0x55);
+    }
+
+    #[test]
+    fn rtc_needs_ram_enabled_and_a_timer() {
+        let mut mbc = mbc3_with_timer();
+        mbc.write_rom(0x4000, 0x08);
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        let mut mbc = Mbc3::new(banked_rom(4), RAM_BANK_SIZE, false, RtcClock::Cycles);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        assert_eq!(mbc.save_rtc(), None);
+    }
+
+    #[test]
+    fn rtc_state_is_saved_after_the_ram() {
+        let mut mbc = mbc3_with_timer();
+        mbc.write_rom(0x4000, 0x09);
+        mbc.write_ram(0xA000, 12);
+        let state = mbc.save_rtc().unwrap();
+        assert_eq!(state.len(), RTC_SAVE_SIZE);
+
+        let mut loaded = mbc3_with_timer();
+        loaded.load_rtc(&state);
+        loaded.write_rom(0x4000, 0x09);
+        loaded.write_rom(0x6000, 0x00);
+        loaded.write_rom(0x6000, 0x01);
+        assert_eq!(loaded.read_ram(0xA000), 12);
+    }
+}
@@ -0,0 +1,184 @@
+use super::{MemoryBankController, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    //9 bit ROM bank number, bank 0 can be mapped at 0x4000-0x7FFF
+    rom_bank: u16,
+    ram_bank: u8,
+    //On rumble carts bit 3 of the RAM bank register drives the motor
+    has_rumble: bool,
+    motor: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            motor: false,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+        let offset = self.ram_bank as usize * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+        Some(offset % self.ram.len())
+    }
+}
+
+impl MemoryBankController for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        let bank = bank % (self.rom.len() / ROM_BANK_SIZE).max(1);
+        let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, address: u16, val: u8) {
+        match address {
+            //Unlike MBC1, the whole byte has to be 0x0A
+            0x0000..=0x1FFF => self.ram_enabled = val == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | val as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((val as u16 & 0x01) << 8),
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.ram_bank = val & 0x07;
+                self.motor = val & 0x08 != 0;
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x0F,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.ram_offset(address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, val: u8) -> bool {
+        match self.ram_offset(address) {
+            Some(offset) => {
+                self.ram[offset] = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rumble(&self) -> bool {
+        self.motor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::tests::banked_rom;
+
+    fn mapped_bank(mbc: &Mbc5) -> u16 {
+        mbc.read_rom(0x4000) as u16 | (mbc.read_rom(0x4001) as u16) << 8
+    }
+
+    #[test]
+    fn rom_bank_uses_nine_bits() {
+        let mut mbc = Mbc5::new(banked_rom(512), 0, false);
+        mbc.write_rom(0x2000, 0xFF);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(mapped_bank(&mbc), 0x1FF);
+        //Only bit 0 of the high register is wired
+        mbc.write_rom(0x3FFF, 0xFE);
+        assert_eq!(mapped_bank(&mbc), 0x0FF);
+        //The low register keeps the high bit
+        mbc.write_rom(0x3000, 0x01);
+        mbc.write_rom(0x2FFF, 0x02);
+        assert_eq!(mapped_bank(&mbc), 0x102);
+    }
+
+    #[test]
+    fn bank_zero_can_be_mapped_high() {
+        let mut mbc = Mbc5::new(banked_rom(4), 0, false);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mapped_bank(&mbc), 0);
+        assert_eq!(mbc.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn rom_bank_wraps_to_rom_size() {
+        let mut mbc = Mbc5::new(banked_rom(8), 0, false);
+        mbc.write_rom(0x2000, 0x0B);
+        assert_eq!(mapped_bank(&mbc), 3);
+    }
+
+    #[test]
+    fn ram_enable_requires_exact_value() {
+        let mut mbc = Mbc5::new(banked_rom(2), 0x2000, false);
+        mbc.write_rom(0x0000, 0x1A);
+        mbc.write_ram(0xA000, 0x42);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x42);
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+        mbc.write_rom(0x1FFF, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(banked_rom(2), 16 * RAM_BANK_SIZE, false);
+        mbc.write_rom(0x0000, 0x0A);
+        for bank in 0..16 {
+            mbc.write_rom(0x4000, bank);
+            mbc.write_ram(0xA000, bank + 0x10);
+        }
+        for bank in 0..16 {
+            mbc.write_rom(0x5FFF, bank);
+            assert_eq!(mbc.read_ram(0xA000), bank + 0x10);
+        }
+    }
+
+    #[test]
+    fn rumble_bit_does_not_select_ram() {
+        let mut mbc = Mbc5::new(banked_rom(2), 8 * RAM_BANK_SIZE, true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_ram(0xA000, 0x11);
+        assert!(!mbc.rumble());
+        //Motor on, same RAM bank
+        mbc.write_rom(0x4000, 0x09);
+        assert!(mbc.rumble());
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+        mbc.write_rom(0x4000, 0x01);
+        assert!(!mbc.rumble());
+    }
+
+    #[test]
+    fn bit_3_selects_ram_without_rumble() {
+        let mut mbc = Mbc5::new(banked_rom(2), 16 * RAM_BANK_SIZE, false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x00);
+        mbc.write_ram(0xA000, 0x11);
+        mbc.write_rom(0x4000, 0x08);
+        assert!(!mbc.rumble());
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+    }
+}
@@ -0,0 +1,296 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CYCLES_PER_SECOND: u64 = 4_194_304;
+//5 current registers, 5 latched registers and a 64 bit timestamp, as written by BGB/VBA-M
+pub const RTC_SAVE_SIZE: usize = 48;
+//Older saves store a 32 bit timestamp
+pub const RTC_SAVE_SIZE_LEGACY: usize = 44;
+
+const DH_DAY_HIGH: u8 = 0x01;
+const DH_HALT: u8 = 0x40;
+const DH_DAY_CARRY: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcClock {
+    //Follow the host clock, like a real cartridge does
+    WallClock,
+    //Advance from emulated cycles so runs are reproducible
+    Cycles,
+}
+
+#[derive(Default, Clone, Copy)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    day_low: u8,
+    //bit 0: day bit 8, bit 6: halt, bit 7: day counter carry
+    day_high: u8,
+}
+
+impl RtcRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds & 0x3F,
+            0x09 => self.minutes & 0x3F,
+            0x0A => self.hours & 0x1F,
+            0x0B => self.day_low,
+            0x0C => self.day_high & (DH_DAY_HIGH | DH_HALT | DH_DAY_CARRY),
+            _ => 0xFF,
+        }
+    }
+
+    fn to_words(self) -> [u32; 5] {
+        [self.seconds, self.minutes, self.hours, self.day_low, self.day_high].map(|r| r as u32)
+    }
+
+    fn from_words(words: &[u32]) -> RtcRegisters {
+        RtcRegisters {
+            seconds: words[0] as u8,
+            minutes: words[1] as u8,
+            hours: words[2] as u8,
+            day_low: words[3] as u8,
+            day_high: words[4] as u8,
+        }
+    }
+}
+
+pub struct Rtc {
+    clock: RtcClock,
+    current: RtcRegisters,
+    latched: RtcRegisters,
+    //Last value written to 0x6000-0x7FFF, latching happens on a 0 -> 1 write
+    latch_write: u8,
+    //Cycles not yet turned into a full second
+    cycles: u64,
+    //Host time of the last wall clock update (seconds since epoch)
+    last_sync: u64,
+}
+
+impl Rtc {
+    pub fn new(clock: RtcClock) -> Rtc {
+        Rtc {
+            clock,
+            current: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            latch_write: 0xFF,
+            cycles: 0,
+            last_sync: unix_time(),
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if self.clock != RtcClock::Cycles {
+            return;
+        }
+        self.cycles += cycles as u64;
+        let seconds = self.cycles / CYCLES_PER_SECOND;
+        self.cycles %= CYCLES_PER_SECOND;
+        self.advance(seconds);
+    }
+
+    fn sync(&mut self) {
+        if self.clock != RtcClock::WallClock {
+            return;
+        }
+        let now = unix_time();
+        self.advance(now.saturating_sub(self.last_sync));
+        self.last_sync = now;
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        if seconds == 0 || self.current.day_high & DH_HALT != 0 {
+            return;
+        }
+        let r = &mut self.current;
+        let mut total = r.seconds as u64 + seconds;
+        r.seconds = (total % 60) as u8;
+        total = r.minutes as u64 + total / 60;
+        r.minutes = (total % 60) as u8;
+        total = r.hours as u64 + total / 60;
+        r.hours = (total % 24) as u8;
+        let days = (((r.day_high & DH_DAY_HIGH) as u64) << 8 | r.day_low as u64) + total / 24;
+        if days > 0x1FF {
+            r.day_high |= DH_DAY_CARRY;
+        }
+        r.day_low = days as u8;
+        r.day_high = (r.day_high & !DH_DAY_HIGH) | ((days >> 8) as u8 & DH_DAY_HIGH);
+    }
+
+    pub fn write_latch(&mut self, val: u8) {
+        if self.latch_write == 0x00 && val == 0x01 {
+            self.sync();
+            self.latched = self.current;
+        }
+        self.latch_write = val;
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    pub fn write(&mut self, register: u8, val: u8) {
+        self.sync();
+        match register {
+            0x08 => {
+                self.current.seconds = val & 0x3F;
+                //Writing the seconds resets the sub-second divider
+                self.cycles = 0;
+            }
+            0x09 => self.current.minutes = val & 0x3F,
+            0x0A => self.current.hours = val & 0x1F,
+            0x0B => self.current.day_low = val,
+            0x0C => self.current.day_high = val & (DH_DAY_HIGH | DH_HALT | DH_DAY_CARRY),
+            _ => (),
+        }
+    }
+
+    //Little endian block appended to the .sav file by other emulators
+    pub fn save_state(&mut self) -> Vec<u8> {
+        self.sync();
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for word in self.current.to_words().iter().chain(self.latched.to_words().iter()) {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        let timestamp = match self.clock {
+            RtcClock::WallClock => self.last_sync,
+            RtcClock::Cycles => unix_time(),
+        };
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        data
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) {
+        if data.len() != RTC_SAVE_SIZE && data.len() != RTC_SAVE_SIZE_LEGACY {
+            return;
+        }
+        let words: Vec<u32> = data[..40]
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        self.current = RtcRegisters::from_words(&words[0..5]);
+        self.latched = RtcRegisters::from_words(&words[5..10]);
+        let timestamp = if data.len() == RTC_SAVE_SIZE {
+            u64::from_le_bytes(data[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64
+        };
+        //Catch up with the time spent while the emulator was closed
+        if self.clock == RtcClock::WallClock {
+            self.last_sync = timestamp;
+            self.sync();
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECONDS: u8 = 0x08;
+    const MINUTES: u8 = 0x09;
+    const HOURS: u8 = 0x0A;
+    const DAY_LOW: u8 = 0x0B;
+    const DAY_HIGH: u8 = 0x0C;
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn latches_on_0_to_1_write() {
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.tick(5 * CYCLES_PER_SECOND as u32);
+        assert_eq!(rtc.read(SECONDS), 0);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 5);
+        //Writing 1 again without a 0 first doesn't latch
+        rtc.tick(CYCLES_PER_SECOND as u32);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(SECONDS), 5);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 6);
+    }
+
+    #[test]
+    fn halt_bit_freezes_time() {
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.write(DAY_HIGH, DH_HALT);
+        rtc.tick(10 * CYCLES_PER_SECOND as u32);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 0);
+        rtc.write(DAY_HIGH, 0);
+        rtc.tick(10 * CYCLES_PER_SECOND as u32);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 10);
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_the_carry() {
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.write(SECONDS, 59);
+        rtc.write(MINUTES, 59);
+        rtc.write(HOURS, 23);
+        rtc.write(DAY_LOW, 0xFF);
+        rtc.write(DAY_HIGH, DH_DAY_HIGH);
+        rtc.tick(CYCLES_PER_SECOND as u32);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 0);
+        assert_eq!(rtc.read(HOURS), 0);
+        assert_eq!(rtc.read(DAY_LOW), 0);
+        assert_eq!(rtc.read(DAY_HIGH), DH_DAY_CARRY);
+    }
+
+    #[test]
+    fn save_state_round_trip() {
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.write(MINUTES, 42);
+        rtc.write(DAY_LOW, 0x34);
+        latch(&mut rtc);
+        rtc.write(SECONDS, 17);
+        let data = rtc.save_state();
+        assert_eq!(data.len(), RTC_SAVE_SIZE);
+
+        let mut loaded = Rtc::new(RtcClock::Cycles);
+        loaded.load_state(&data);
+        //Latched registers come back as they were
+        assert_eq!(loaded.read(SECONDS), 0);
+        assert_eq!(loaded.read(MINUTES), 42);
+        assert_eq!(loaded.read(DAY_LOW), 0x34);
+        latch(&mut loaded);
+        assert_eq!(loaded.read(SECONDS), 17);
+    }
+
+    #[test]
+    fn loads_the_legacy_block() {
+        let mut data = Vec::new();
+        for word in [30u32, 20, 10, 5, 0, 1, 2, 3, 4, 0] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(data.len(), RTC_SAVE_SIZE_LEGACY);
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.load_state(&data);
+        assert_eq!(rtc.read(SECONDS), 1);
+        assert_eq!(rtc.read(DAY_LOW), 4);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 30);
+        assert_eq!(rtc.read(HOURS), 10);
+    }
+
+    #[test]
+    fn ignores_blocks_of_other_sizes() {
+        let mut rtc = Rtc::new(RtcClock::Cycles);
+        rtc.load_state(&[0x3B; 40]);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS), 0);
+    }
+}
@@ -1,6 +1,25 @@
+use log::trace;
+use crate::interrupt::{Interrupt, InterruptController};
 use crate::tile::extract_tile;
 
 
+//Cycles per scanline, 154 lines per frame
+pub const CYCLES_PER_LINE: u64 = 456;
+const LINES_PER_FRAME: u8 = 154;
+//Dots spent in mode 2 (OAM scan) and mode 3 (drawing), the rest of a visible line is HBlank.
+//Mode 3 really lasts 172 to 289 dots depending on scrolling, window and sprites.
+pub const OAM_SCAN_CYCLES: u64 = 80;
+pub const DRAWING_CYCLES: u64 = 172;
+
+//STAT bits 0-1
+pub const MODE_HBLANK: u8 = 0;
+pub const MODE_VBLANK: u8 = 1;
+pub const MODE_OAM_SCAN: u8 = 2;
+pub const MODE_DRAWING: u8 = 3;
+const STAT_MODE: u8 = 0x03;
+const STAT_LYC_EQUAL: u8 = 0x04;
+const STAT_LYC_INTERRUPT: u8 = 0x40;
+
 pub struct PPU {
     pub lcdc:u8,
     pub lcds:u8,
@@ -16,10 +35,15 @@ pub struct PPU {
     pub video_buffer:[u8;160*144],
     pub oam:[u8;0xA0],
     pub vram:[u8;0x2000],
-    pub bg_tileset:[u8;256*256],
-    pub win_tileset:[u8;256*256],
-    pub vblank_interrupt:u8,
-    pub stat_interrupt:u8
+    //Boxed so the emulator can be moved around without 128 KB stack copies
+    pub bg_tileset:Box<[u8;256*256]>,
+    pub win_tileset:Box<[u8;256*256]>,
+}
+
+impl Default for PPU{
+    fn default()->PPU{
+        PPU::new()
+    }
 }
 
 impl PPU{
@@ -27,7 +51,8 @@ impl PPU{
     pub fn new()->PPU{
         PPU{
             lcdc: 0x91,
-            lcds: 0,
+            //Line 0 starts in OAM scan, with LY = LYC
+            lcds: MODE_OAM_SCAN | STAT_LYC_EQUAL,
             scy: 0,
             scx: 0,
             ly: 0,
@@ -40,41 +65,65 @@ impl PPU{
             video_buffer: [0u8;160*144],
             oam: [0u8;0xA0],
             vram: [0;0x2000],
-            bg_tileset: [0u8;256*256],
-            win_tileset: [0u8;256*256],
-            vblank_interrupt: 0,
-            stat_interrupt: 0,
+            bg_tileset: Box::new([0u8;256*256]),
+            win_tileset: Box::new([0u8;256*256]),
         }
     }
 
-    pub fn ppu_step(&mut self){
-        if self.get_bit(self.lcdc, 7) == 1 {
-            if self.ly == self.lyc && (self.lcds & 0x40) > 0{
-                //raise a STAT interrupt
-                self.stat_interrupt = 1;
-            }
-            match self.ly{
-                144 => {
-                    self.ly += 1;
-                    // raise VBlank interrupt
-                    self.vblank_interrupt = 1;
-                    
-                }
-                145..=153 => self.ly += 1,
-                154 => self.ly = 0,
-                _=>()
-                
-            }
-            if self.ly < 144 {
-                self.bg_tileset = self.tilesets("bg");
-                self.win_tileset = self.tilesets("win");
-                self.render_line();
-                self.ly+= 1;
+    //End of a line: draw it if it is visible and move LY to the next one
+    pub fn ppu_step(&mut self,interrupts:&mut InterruptController){
+        if self.get_bit(self.lcdc, 7) == 0 {
+            //LY stays at 0, in HBlank, while the LCD is off
+            self.ly = 0;
+            self.lcds &= !STAT_MODE;
+            return;
+        }
+        if self.ly < 144 {
+            *self.bg_tileset = self.tilesets("bg");
+            *self.win_tileset = self.tilesets("win");
+            self.render_line();
+        }
+        self.ly = (self.ly + 1) % LINES_PER_FRAME;
+        if self.ly == self.lyc {
+            self.lcds |= STAT_LYC_EQUAL;
+            if self.lcds & STAT_LYC_INTERRUPT != 0 {
+                interrupts.request(Interrupt::Stat);
+                trace!(target: "ppu", "LY=LYC at line {}", self.ly);
             }
-        }   
+        } else {
+            self.lcds &= !STAT_LYC_EQUAL;
+        }
+        if self.ly == 144 {
+            interrupts.request(Interrupt::VBlank);
+            trace!(target: "ppu", "VBlank");
+            self.set_mode(MODE_VBLANK, interrupts);
+        } else if self.ly < 144 {
+            self.set_mode(MODE_OAM_SCAN, interrupts);
+        }
     }
 
-    
+    pub fn mode(&self)->u8{
+        self.lcds & STAT_MODE
+    }
+
+    //Modes 0, 1 and 2 raise a STAT interrupt when enabled by STAT bits 3, 4 and 5
+    pub fn set_mode(&mut self,mode:u8,interrupts:&mut InterruptController){
+        self.lcds = (self.lcds & !STAT_MODE) | mode;
+        if mode != MODE_DRAWING && self.lcds & (0x08 << mode) != 0 {
+            interrupts.request(Interrupt::Stat);
+            trace!(target: "ppu", "mode {} interrupt at line {}", mode, self.ly);
+        }
+    }
+
+    //Bit 7 is unused and reads as 1
+    pub fn stat_read(&self)->u8{
+        0x80 | self.lcds
+    }
+
+    //The mode and LY=LYC bits are read-only
+    pub fn stat_write(&mut self,val:u8){
+        self.lcds = (val & 0x78) | (self.lcds & (STAT_MODE | STAT_LYC_EQUAL));
+    }
 
     fn get_bit(&self,byte:u8,bit:u8)->u8{
         (byte >> bit) & 1
@@ -153,9 +202,9 @@ impl PPU{
         let tilemap = self.tile_map(bit);
         let mut tile_line = 0;
         let mut background = [0u8;256*256];
-        let mut pos_in_line = 0u32;
         //For each address in tilemap
-        for i in tilemap.0..tilemap.1 {
+        for (pos_in_line, i) in (tilemap.0..tilemap.1).enumerate() {
+            let pos_in_line = pos_in_line as u32;
             //Read tile index from VRAM
            
             let tile_index =  self.vram_read(i);
@@ -171,7 +220,7 @@ impl PPU{
             //Get pixels from tile
             let tile_pixels = extract_tile(tile);
             
-            if pos_in_line%32 == 0 && pos_in_line != 0{
+            if pos_in_line.is_multiple_of(32) && pos_in_line != 0{
                 tile_line += 8;
             }
             //For each  line
@@ -181,105 +230,83 @@ impl PPU{
                     background[(256*tile_line+256*k+l+8*(pos_in_line%32)) as usize] = tile_pixels[k as usize][l as usize];
                 }
             }
-            pos_in_line += 1;
-            
         }
     
         background        
     }
 
-    fn is_visible(&self,x:u8,y:u8)->bool{
-        let h = match self.get_bit(self.lcdc,2){
-            0 => 8,
-            1 => 16,
-            _ =>panic!()
-        };
-        (x != 0) && (self.ly + 16 >= y) && (self.ly < y + h)
+    //Sprites on the current line, at most 10 in OAM order
+    fn line_sprites(&self,height:u8)->Vec<usize>{
+        let line = self.ly as u16 + 16;
+        (0..40)
+            .map(|i| i * 4)
+            .filter(|&i| {
+                let y = self.oam[i] as u16;
+                line >= y && line < y + height as u16
+            })
+            .take(10)
+            .collect()
     }
-        
+
     fn render_sprites(&mut self){
-        if self.get_bit(self.lcdc, 1) == 1 {
-            for i in 0..40 {
-                let y = self.oam[i];
-                let x = self.oam[i+1];
-                let tile_index = self.oam[i+2];
-                let tile_address = (tile_index as u16) * 16;
-                let flags = self.oam[i+3];
-                let ly = self.ly;
-                let priority =self.get_bit(flags, 7);
-                let y_flip = self.get_bit(flags, 6);
-                let x_flip = self.get_bit(flags, 5);
-                if self.is_visible(x, y){
-                    match self.get_bit(self.lcdc, 2){
-                        0 =>{
-                            let mut tile = [0u8;16];
-                            for j in 0..16 {
-                                tile[j as usize] = self.vram_read(tile_address+j);
-                            }
-                            let tile_pixels = extract_tile(tile);
-                            
-                            for k in (x-8)..x{
-                                if (priority == 0) || (priority == 1 && self.video_buffer[(ly*160+k) as usize] == 0) && !self.pixel_in_window(k, ly-y+16){
-                                    let mut pixel = tile_pixels[k as usize][(ly-y+16) as usize];
-                                    //Y flip
-                                    if y_flip == 1 {
-                                        pixel = tile_pixels[k as usize][7-(ly-y+16) as usize];
-                                    }
-                                    //X flip
-                                    if  x_flip == 1 {
-                                        pixel = tile_pixels[7-k as usize][(ly-y+16) as usize];
-                                    }
-                                    if x_flip == 1 && y_flip == 1 {
-                                        pixel = tile_pixels[7-k as usize][7-(ly-y+16) as usize];
-                                    }
-                                    self.video_buffer[(ly*160+k) as usize] = pixel;
-                                }
-                            }
-                        },
-                        1 =>{
-                            let mut tile1 = [0u8;16];
-                            let mut tile2 = [0u8;16];
-                            for j in 0..16 {
-                                tile1[j as usize] = self.vram_read(tile_address+j);
-                            }
-                            let tile1_pixels = extract_tile(tile1);
-                            for j in 0..16 {
-                                tile2[j as usize] = self.vram_read(tile_address+16+j);
-                            }
-                            let tile2_pixels = extract_tile(tile2);
-                            let tile_pixels = self.double_tile(tile1_pixels, tile2_pixels);
-
-                            for k in (x-8)..x{
-                                if (priority == 0) || (priority == 1 && self.video_buffer[(ly*160+k) as usize] == 0){
-                                    let mut pixel = tile_pixels[k as usize][(ly-y+16) as usize];
-                                    //Y flip
-                                    if y_flip == 1 {
-                                        pixel = tile_pixels[k as usize][15-(ly-y+16) as usize];
-                                    }
-                                    //X flip
-                                    if x_flip == 1 {
-                                        pixel = tile_pixels[7-k as usize][(ly-y+16) as usize];
-                                    }
-                                    if x_flip == 1 && y_flip == 1 {
-                                        pixel = tile_pixels[7-k as usize][15-(ly-y+16) as usize];
-                                    }
-                                    self.video_buffer[(ly*160+k) as usize] = pixel;
-                                }
-                            }
-                        }
-                        _ =>panic!()
-                    }
+        if self.get_bit(self.lcdc, 1) == 0 {
+            return;
+        }
+        let height = if self.get_bit(self.lcdc, 2) == 1 { 16 } else { 8 };
+        let ly = self.ly as usize;
+        let background: Vec<u8> = self.video_buffer[ly*160..ly*160+160].to_vec();
+        let mut sprites = self.line_sprites(height);
+        //The sprite with the smallest X, then the first in OAM, is drawn last and ends up on top
+        sprites.sort_by_key(|&i| self.oam[i+1]);
+        for &i in sprites.iter().rev() {
+            let y = self.oam[i];
+            let x = self.oam[i+1];
+            let flags = self.oam[i+3];
+            let priority = self.get_bit(flags, 7);
+            let y_flip = self.get_bit(flags, 6);
+            let x_flip = self.get_bit(flags, 5);
+            let tile_pixels = self.sprite_tile(self.oam[i+2], height);
+            let mut row = ly + 16 - y as usize;
+            if y_flip == 1 {
+                row = height as usize - 1 - row;
+            }
+            //X is the right edge plus 8, sprites can be partly off screen
+            for col in 0..8 {
+                let screen_x = x as usize + col;
+                if !(8..168).contains(&screen_x) {
+                    continue;
                 }
+                let screen_x = screen_x - 8;
+                let pixel = if x_flip == 1 { tile_pixels[row][7-col] } else { tile_pixels[row][col] };
+                //Colour 0 is transparent, with the priority bit set BG colours 1-3 stay on top
+                if pixel == 0 || (priority == 1 && background[screen_x] != 0) {
+                    continue;
+                }
+                self.video_buffer[ly*160+screen_x] = pixel;
+            }
+        }
+    }
+
+    //Sprites always use 0x8000 addressing, 8x16 sprites ignore bit 0 of the tile index
+    fn sprite_tile(&self,tile_index:u8,height:u8)->Vec<[u8;8]>{
+        let tile_at = |index:u8| {
+            let mut tile = [0u8;16];
+            for j in 0..16 {
+                tile[j as usize] = self.vram_read(index as u16 * 16 + j);
             }
-        } 
+            extract_tile(tile)
+        };
+        if height == 16 {
+            self.double_tile(tile_at(tile_index & 0xFE), tile_at(tile_index | 0x01)).to_vec()
+        } else {
+            tile_at(tile_index).to_vec()
+        }
     }
 
     fn double_tile(&self,tile1:[[u8; 8]; 8],tile2:[[u8; 8]; 8])->[[u8; 8]; 16]{
         let mut tile = [[0u8; 8]; 16];
-        for i in 0..8 {
-            tile[i] = tile1[i];
-            tile[i + 8] = tile2[i];
-        }
+        tile[..8].copy_from_slice(&tile1);
+        tile[8..].copy_from_slice(&tile2);
         tile
     }
     
@@ -300,4 +327,103 @@ impl PPU{
         }
     }
     
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //LCD, BG and sprites on, tile data at 0x8000. Tile 0 is blank, tile 1 has
+    //colour 3 in the top left pixel and a last row of colour 1, tile 2 is all
+    //colour 2 and tile 3 has a first row of colour 3.
+    fn ppu() -> PPU {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x93;
+        ppu.vram[0x10..0x12].copy_from_slice(&[0x80, 0x80]);
+        ppu.vram[0x1E..0x20].copy_from_slice(&[0xFF, 0x00]);
+        for row in 0..8 {
+            ppu.vram[0x20 + row * 2 + 1] = 0xFF;
+        }
+        ppu.vram[0x30..0x32].copy_from_slice(&[0xFF, 0xFF]);
+        ppu
+    }
+
+    fn sprite(ppu: &mut PPU, index: usize, x: u8, tile: u8, flags: u8) {
+        //Y 16 puts the top of the sprite on line 0
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[16, x + 8, tile, flags]);
+    }
+
+    fn line(ppu: &mut PPU, ly: u8) -> Vec<u8> {
+        ppu.ly = ly;
+        ppu.render_line();
+        ppu.video_buffer[ly as usize * 160..ly as usize * 160 + 160].to_vec()
+    }
+
+    #[test]
+    fn sprites_are_four_oam_bytes_apart() {
+        let mut ppu = ppu();
+        sprite(&mut ppu, 1, 10, 1, 0);
+        assert_eq!(line(&mut ppu, 0)[9..12], [0, 3, 0]);
+        assert_eq!(line(&mut ppu, 7)[9..19], [0, 1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn sprites_flip_on_both_axes() {
+        let mut ppu = ppu();
+        sprite(&mut ppu, 0, 10, 1, 0x20);
+        assert_eq!(line(&mut ppu, 0)[10..18], [0, 0, 0, 0, 0, 0, 0, 3]);
+        sprite(&mut ppu, 0, 10, 1, 0x40);
+        assert_eq!(line(&mut ppu, 0)[10..18], [1; 8]);
+        assert_eq!(line(&mut ppu, 7)[10..18], [3, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bg_priority_only_hides_sprites_behind_colours_1_to_3() {
+        let mut ppu = ppu();
+        ppu.bg_tileset[256 * 7 + 14..256 * 7 + 256].fill(2);
+        sprite(&mut ppu, 0, 10, 1, 0x80);
+        assert_eq!(line(&mut ppu, 7)[10..18], [1, 1, 1, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn ten_sprites_per_line() {
+        let mut ppu = ppu();
+        for i in 0..11 {
+            sprite(&mut ppu, i, 12 * i as u8, 2, 0);
+        }
+        let line = line(&mut ppu, 0);
+        assert_eq!(line[108], 2);
+        assert_eq!(line[120], 0);
+    }
+
+    #[test]
+    fn smaller_x_then_oam_order_wins() {
+        let mut ppu = ppu();
+        sprite(&mut ppu, 0, 4, 1, 0);
+        sprite(&mut ppu, 1, 0, 2, 0);
+        sprite(&mut ppu, 2, 30, 1, 0);
+        sprite(&mut ppu, 3, 30, 2, 0);
+        let line = line(&mut ppu, 7);
+        assert_eq!(line[0..12], [2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1]);
+        assert_eq!(line[30], 1);
+    }
+
+    #[test]
+    fn tall_sprites_use_an_even_odd_tile_pair() {
+        let mut ppu = ppu();
+        ppu.lcdc |= 0x04;
+        sprite(&mut ppu, 0, 10, 3, 0);
+        assert_eq!(line(&mut ppu, 0)[10..18], [2; 8]);
+        assert_eq!(line(&mut ppu, 8)[10..18], [3; 8]);
+        assert_eq!(line(&mut ppu, 16)[10..18], [0; 8]);
+    }
+
+    #[test]
+    fn sprites_clip_at_the_screen_edges() {
+        let mut ppu = ppu();
+        ppu.oam[0..4].copy_from_slice(&[16, 4, 2, 0]);
+        ppu.oam[4..8].copy_from_slice(&[16, 164, 2, 0]);
+        let line = line(&mut ppu, 0);
+        assert_eq!(line[0..5], [2, 2, 2, 2, 0]);
+        assert_eq!(line[155..160], [0, 2, 2, 2, 2]);
+    }
+}
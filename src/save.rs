@@ -0,0 +1,113 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::mbc::{MemoryBankController, RTC_SAVE_SIZE, RTC_SAVE_SIZE_LEGACY};
+
+//"game.gb" is saved as "game.sav", next to it unless a save directory is given
+pub fn save_path(rom_path: &Path, save_dir: Option<&Path>) -> PathBuf {
+    let path = rom_path.with_extension("sav");
+    match (save_dir, path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path,
+    }
+}
+
+//Raw RAM dump, followed by the RTC block for MBC3 carts with a timer
+pub fn load(path: &Path, mbc: &mut dyn MemoryBankController) -> io::Result<()> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        //No save yet, the cartridge starts with blank RAM
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let ram_len = mbc.ram().len().min(data.len());
+    mbc.load_ram(&data[..ram_len]);
+    let rtc = &data[ram_len..];
+    if rtc.len() == RTC_SAVE_SIZE || rtc.len() == RTC_SAVE_SIZE_LEGACY {
+        mbc.load_rtc(rtc);
+    }
+    Ok(())
+}
+
+pub fn write(path: &Path, mbc: &mut dyn MemoryBankController) -> io::Result<()> {
+    let mut data = mbc.ram().to_vec();
+    if let Some(rtc) = mbc.save_rtc() {
+        data.extend_from_slice(&rtc);
+    }
+    //Write next to the save first so a crash never leaves a truncated file
+    let tmp = path.with_extension("sav.tmp");
+    fs::write(&tmp, &data)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::{Mbc3, NoMbc, RtcClock, ROM_BANK_SIZE};
+
+    //Fresh directory under the system temp dir, removed by the caller
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("emulator-save-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mbc_with_ram(size: usize) -> NoMbc {
+        NoMbc::new(vec![0; 2 * ROM_BANK_SIZE], size)
+    }
+
+    #[test]
+    fn save_path_replaces_the_extension() {
+        assert_eq!(save_path(Path::new("roms/game.gb"), None), Path::new("roms/game.sav"));
+        assert_eq!(save_path(Path::new("roms/game"), None), Path::new("roms/game.sav"));
+        assert_eq!(save_path(Path::new("roms/game.gb"), Some(Path::new("saves"))), Path::new("saves/game.sav"));
+    }
+
+    #[test]
+    fn write_then_load_round_trip() {
+        let dir = temp_dir("roundtrip");
+        let path = dir.join("game.sav");
+        let mut mbc = mbc_with_ram(0x2000);
+        mbc.ram_mut()[0] = 0x12;
+        mbc.ram_mut()[0x1FFF] = 0x34;
+        write(&path, &mut mbc).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 0x2000);
+        assert!(!path.with_extension("sav.tmp").exists());
+
+        let mut loaded = mbc_with_ram(0x2000);
+        load(&path, &mut loaded).unwrap();
+        assert_eq!(loaded.ram(), mbc.ram());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rtc_block_follows_the_ram() {
+        let dir = temp_dir("rtc");
+        let path = dir.join("game.sav");
+        let mut mbc = Mbc3::new(vec![0; 2 * ROM_BANK_SIZE], 0x2000, true, RtcClock::Cycles);
+        write(&path, &mut mbc).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 0x2000 + RTC_SAVE_SIZE);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_file_keeps_blank_ram() {
+        let dir = temp_dir("missing");
+        let mut mbc = mbc_with_ram(0x2000);
+        load(&dir.join("none.sav"), &mut mbc).unwrap();
+        assert!(mbc.ram().iter().all(|&b| b == 0));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn short_file_fills_the_start_of_ram() {
+        let dir = temp_dir("short");
+        let path = dir.join("game.sav");
+        fs::write(&path, [0xAA; 0x100]).unwrap();
+        let mut mbc = mbc_with_ram(0x2000);
+        load(&path, &mut mbc).unwrap();
+        assert_eq!(mbc.ram()[0xFF], 0xAA);
+        assert_eq!(mbc.ram()[0x100], 0x00);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+//Device work due at a known cycle, so nothing has to be polled every instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    //End of a 456 cycle scanline
+    PpuLine,
+    //OAM scan to drawing and drawing to HBlank, inside a visible line
+    PpuMode,
+    DivTick,
+    TimaTick,
+    SerialBit,
+    //Copy of the next OAM DMA byte
+    OamDma,
+}
+
+//Pending events, at most one of each kind, ordered by due cycle
+#[derive(Default)]
+pub struct Scheduler {
+    events: Vec<(u64, Event)>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    //Replaces the pending event of the same kind
+    pub fn schedule(&mut self, at: u64, event: Event) {
+        self.cancel(event);
+        //After the events due at the same cycle, they run in the order they were scheduled
+        let index = self.events.partition_point(|&(due, _)| due <= at);
+        self.events.insert(index, (at, event));
+    }
+
+    pub fn cancel(&mut self, event: Event) {
+        self.events.retain(|&(_, e)| e != event);
+    }
+
+    //Earliest event due at or before now, with the cycle it was due at
+    pub fn pop(&mut self, now: u64) -> Option<(u64, Event)> {
+        match self.events.first() {
+            Some(&(due, _)) if due <= now => Some(self.events.remove(0)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_come_out_in_due_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(456, Event::PpuLine);
+        scheduler.schedule(256, Event::DivTick);
+        scheduler.schedule(256, Event::TimaTick);
+        assert_eq!(scheduler.pop(255), None);
+        assert_eq!(scheduler.pop(500), Some((256, Event::DivTick)));
+        assert_eq!(scheduler.pop(500), Some((256, Event::TimaTick)));
+        assert_eq!(scheduler.pop(500), Some((456, Event::PpuLine)));
+        assert_eq!(scheduler.pop(500), None);
+    }
+
+    #[test]
+    fn rescheduling_replaces_the_pending_event() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(100, Event::SerialBit);
+        scheduler.schedule(612, Event::SerialBit);
+        assert_eq!(scheduler.pop(611), None);
+        assert_eq!(scheduler.pop(612), Some((612, Event::SerialBit)));
+        scheduler.schedule(100, Event::SerialBit);
+        scheduler.cancel(Event::SerialBit);
+        assert_eq!(scheduler.pop(1000), None);
+    }
+}
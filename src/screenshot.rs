@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+//Grey shades used when no palette is given, from lightest to darkest (0x00RRGGBB)
+pub const GREY: [u32; 4] = [0x00FFFFFF, 0x00A9A9A9, 0x00545454, 0x00000000];
+
+//Turn the shades of the framebuffer into RGB bytes
+pub fn to_rgb(framebuffer: &[u8], palette: &[u32; 4]) -> Vec<u8> {
+    framebuffer
+        .iter()
+        .flat_map(|&shade| {
+            let color = palette[(shade & 0x03) as usize];
+            [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+        })
+        .collect()
+}
+
+//Binary PPM (P6), readable by most image tools without any dependency
+pub fn write_ppm(path: &Path, framebuffer: &[u8], palette: &[u32; 4]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT)?;
+    file.write_all(&to_rgb(framebuffer, palette))?;
+    file.flush()
+}
+
+pub fn write_png(path: &Path, framebuffer: &[u8], palette: &[u32; 4]) -> io::Result<()> {
+    write_rgb_png(path, SCREEN_WIDTH, SCREEN_HEIGHT, &to_rgb(framebuffer, palette))
+}
+
+pub fn write_rgb_png(path: &Path, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgb).map_err(io::Error::other)
+}
+
+//Load a 160x144 screenshot back into shades, the darker the color the higher the shade
+pub fn read_png(path: &Path) -> io::Result<Vec<u8>> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    //Indexed and low bit depth images are turned into 8 bit channels
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(io::Error::other)?;
+    if info.width as usize != SCREEN_WIDTH || info.height as usize != SCREEN_HEIGHT || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected a 160x144 image with 8 bit channels"));
+    }
+    let channels = info.color_type.samples();
+    let shades = buffer[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| {
+            let luma = match pixel.len() {
+                1 | 2 => pixel[0] as u32,
+                _ => (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000,
+            };
+            3 - ((luma + 42) / 85).min(3) as u8
+        })
+        .collect();
+    Ok(shades)
+}
+
+//The format is picked from the extension, PNG unless it is .ppm
+pub fn write(path: &Path, framebuffer: &[u8], palette: &[u32; 4]) -> io::Result<()> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("ppm") => write_ppm(path, framebuffer, palette),
+        _ => write_png(path, framebuffer, palette),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_conversion_uses_palette() {
+        let rgb = to_rgb(&[0, 3, 2], &[0x00112233, 0, 0x00A0B0C0, 0x00FFFFFF]);
+        assert_eq!(rgb, vec![0x11, 0x22, 0x33, 0xFF, 0xFF, 0xFF, 0xA0, 0xB0, 0xC0]);
+    }
+
+    #[test]
+    fn ppm_is_picked_from_the_extension() {
+        let framebuffer = vec![3; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let path = std::env::temp_dir().join(format!("emulator-screenshot-{}.PPM", std::process::id()));
+        write(&path, &framebuffer, &GREY).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let header = b"P6\n160 144\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+        assert!(data[header.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn png_round_trip_keeps_shades() {
+        let framebuffer: Vec<u8> = (0..SCREEN_WIDTH * SCREEN_HEIGHT).map(|i| (i % 7 % 4) as u8).collect();
+        let path = std::env::temp_dir().join(format!("emulator-roundtrip-{}.png", std::process::id()));
+        write_png(&path, &framebuffer, &GREY).unwrap();
+        let shades = read_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(shades, framebuffer);
+    }
+
+    #[test]
+    fn read_png_rejects_other_sizes() {
+        let path = std::env::temp_dir().join(format!("emulator-small-{}.png", std::process::id()));
+        write_rgb_png(&path, 8, 8, &[0; 8 * 8 * 3]).unwrap();
+        let result = read_png(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,116 @@
+use log::debug;
+use crate::interrupt::{Interrupt, InterruptController};
+
+//Cycles per bit with the internal 8192 Hz clock
+pub const CYCLES_PER_BIT: u64 = 512;
+
+//Link cable port, SB (0xFF01) and SC (0xFF02)
+pub struct Serial{
+    sb:u8,
+    sc:u8,
+    bits_left:u8,
+    //Every byte sent, test ROMs print their results this way
+    output:Vec<u8>,
+}
+
+impl Default for Serial{
+    fn default()->Serial{
+        Serial::new()
+    }
+}
+
+impl Serial{
+    pub fn new()->Serial{
+        Serial{
+            sb: 0,
+            sc: 0,
+            bits_left: 0,
+            output: Vec::new(),
+        }
+    }
+
+    //A transfer with the internal clock is running
+    pub fn transferring(&self)->bool{
+        self.bits_left > 0
+    }
+
+    //Shift one bit every CYCLES_PER_BIT, returns true while bits are left
+    pub fn shift(&mut self, interrupts: &mut InterruptController)->bool{
+        if self.bits_left == 0 {
+            return false;
+        }
+        //No cable plugged in, the incoming bits are all 1
+        self.sb = (self.sb << 1) | 1;
+        self.bits_left -= 1;
+        if self.bits_left == 0 {
+            self.sc &= 0x7F;
+            interrupts.request(Interrupt::Serial);
+        }
+        self.bits_left > 0
+    }
+
+    pub fn serial_read(&self,address:u16)->u8{
+        match address{
+            0xFF01 => self.sb,
+            //Unused bits read as 1
+            0xFF02 => self.sc | 0x7E,
+            _=> unreachable!("Invalid serial register address: 0x{:04X}", address),
+        }
+    }
+
+    pub fn serial_write(&mut self,address:u16, val:u8){
+        match address{
+            0xFF01 => self.sb = val,
+            0xFF02 => {
+                self.sc = val;
+                //Transfer start with the internal clock, the external one never ticks without a partner
+                if val & 0x81 == 0x81 {
+                    debug!(target: "serial", "sent {:02X}", self.sb);
+                    self.output.push(self.sb);
+                    self.bits_left = 8;
+                }
+            }
+            _=> unreachable!("Invalid serial register address: 0x{:04X}", address),
+        }
+    }
+
+    pub fn output(&self)->&[u8]{
+        &self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_transfer_completes_after_8_bits() {
+        let mut serial = Serial::new();
+        let mut interrupts = InterruptController::new();
+        serial.serial_write(0xFF01, b'P');
+        serial.serial_write(0xFF02, 0x81);
+        assert_eq!(serial.output(), b"P");
+        for _ in 0..7 {
+            assert!(serial.shift(&mut interrupts));
+        }
+        assert_eq!(interrupts.read_if(), 0xE0);
+        assert_eq!(serial.serial_read(0xFF02), 0xFF);
+        assert!(!serial.shift(&mut interrupts));
+        assert_eq!(interrupts.read_if(), 0xE8);
+        assert_eq!(serial.serial_read(0xFF02), 0x7F);
+        assert_eq!(serial.serial_read(0xFF01), 0xFF);
+    }
+
+    #[test]
+    fn external_clock_does_not_transfer() {
+        let mut serial = Serial::new();
+        let mut interrupts = InterruptController::new();
+        serial.serial_write(0xFF01, 0x42);
+        serial.serial_write(0xFF02, 0x80);
+        assert!(!serial.transferring());
+        assert!(!serial.shift(&mut interrupts));
+        assert_eq!(interrupts.read_if(), 0xE0);
+        assert_eq!(serial.serial_read(0xFF01), 0x42);
+        assert!(serial.output().is_empty());
+    }
+}
@@ -24,7 +24,7 @@ pub fn extract_tile(tile_data: [u8; 16]) -> [[u8; 8]; 8] {
         let byte1 = tile_data[row * 2];
         let byte2 = tile_data[row * 2 + 1];
 
-        for col in 0..8 {
+        for (col, pixel) in tile[row].iter_mut().enumerate() {
             // Calculate the shift amount to extract the color for this pixel
             let shift = 7 - col;
 
@@ -35,7 +35,7 @@ pub fn extract_tile(tile_data: [u8; 16]) -> [[u8; 8]; 8] {
             // Combine the color bits from both bytes to get the final color
             let color = (color2 << 1) | color1;
 
-            tile[row][col] = color;
+            *pixel = color;
         }
     }
     // return 2d array filled with 0, 1, 2 and 3.
@@ -1,12 +1,21 @@
+use log::trace;
+use crate::interrupt::{Interrupt, InterruptController};
+
+//Cycles between DIV increments (16384 Hz)
+pub const DIV_PERIOD: u64 = 256;
+
+//DIV, TIMA, TMA and TAC (0xFF04-0xFF07), clocked by the scheduler
 pub struct Timer{
-    div:u16,
+    div:u8,
     tima:u8,
     tma:u8,
     tac:u8,
-    pub cycles_counter:u32,
-    div_counter:u32,
-    tima_counter:u32,
-    pub timer_interrupt:u8,
+}
+
+impl Default for Timer{
+    fn default()->Timer{
+        Timer::new()
+    }
 }
 
 impl Timer{
@@ -16,54 +25,48 @@ impl Timer{
             tima: 0,
             tma: 0,
             tac: 0,
-            cycles_counter: 0,
-            div_counter: 0,
-            tima_counter: 0,
-            timer_interrupt:0,
         }
     }
 
-    pub fn timer_tick(&mut self, cycles: u32,should_cycles:u8){
-        if (should_cycles >> 7) & 1 == 1 {
-            self.cycles_counter += cycles;
+    pub fn div_tick(&mut self){
+        self.div = self.div.wrapping_add(1);
+    }
+
+    //Cycles between TIMA increments, None while TAC stops it
+    pub fn tima_period(&self)->Option<u64>{
+        if (self.tac >> 2) & 1 == 0{
+            return None;
         }
-        if (self.tac >> 2) & 1 != 0{
-            self.tima_counter += cycles;
-            let inc_rate = match self.tac & 0x03 {
-                0 => 1024, //4096 kHz
-                1 => 16, // 262 144 kHz
-                2=> 64, //65 536 kHz
-                3 => 256, //16 384 kHz
-                _=> unreachable!()
-            };
-            if self.tima_counter >= inc_rate {
-                if self.tima == 0xFF{
-                    self.tima = self.tma;
-                    //Trigger timer interrupt
-                    self.timer_interrupt = 1;
-                }else{
-                    let _ = self.tima.wrapping_add(1);
-                }
-                self.tima_counter -= inc_rate;
-            }
+        Some(match self.tac & 0x03 {
+            0 => 1024, //4096 Hz
+            1 => 16, //262144 Hz
+            2 => 64, //65536 Hz
+            _ => 256, //16384 Hz
+        })
+    }
+
+    pub fn tima_tick(&mut self,interrupts:&mut InterruptController){
+        if self.tima == 0xFF{
+            self.tima = self.tma;
+            //Trigger timer interrupt
+            interrupts.request(Interrupt::Timer);
+            trace!(target: "timer", "TIMA overflow, reloaded with {:02X}", self.tma);
+        }else{
+            self.tima = self.tima.wrapping_add(1);
         }
-        self.div_counter += cycles;
-        if self.div_counter >= 256 {
-            self.div = self.div.wrapping_add(1);
-            self.div_counter -= 256;
-        }   
     }
 
     pub fn timer_read(&self,address:u16)->u8{
         match address{
-            0xFF04 =>self.div as u8,
+            0xFF04 =>self.div,
             0xFF05 =>self.tima,
             0xFF06 =>self.tma,
-            0xFF07 =>self.tac, 
+            0xFF07 =>self.tac,
             _=> unreachable!("Invalid timer register address: 0x{:04X}", address),
         }
     }
-    
+
+    //DIV and TAC writes also move the next ticks, the bus reschedules them
     pub fn timer_write(&mut self,address:u16, val:u8){
         match address{
             0xFF04 =>self.div = 0,
@@ -76,3 +79,31 @@ impl Timer{
 
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tima_counts_and_reloads_from_tma() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.timer_write(0xFF06, 0xF0);
+        timer.timer_write(0xFF05, 0xFE);
+        timer.tima_tick(&mut interrupts);
+        assert_eq!(timer.timer_read(0xFF05), 0xFF);
+        assert_eq!(interrupts.read_if(), 0xE0);
+        timer.tima_tick(&mut interrupts);
+        assert_eq!(timer.timer_read(0xFF05), 0xF0);
+        assert_eq!(interrupts.read_if(), 0xE4);
+    }
+
+    #[test]
+    fn tac_selects_the_period() {
+        let mut timer = Timer::new();
+        assert_eq!(timer.tima_period(), None);
+        timer.timer_write(0xFF07, 0x05);
+        assert_eq!(timer.tima_period(), Some(16));
+        timer.timer_write(0xFF07, 0x03);
+        assert_eq!(timer.tima_period(), None);
+    }
+}
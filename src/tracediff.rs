@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::panic::{self, AssertUnwindSafe};
+use crate::disasm;
+use crate::emulator::Emulator;
+
+//One second of emulated time, a HALT waiting longer than that never wakes up
+const HALT_TIMEOUT: u64 = 4_194_304;
+
+//Lockstep comparison of the emulator against a gameboy-doctor reference log,
+//one "A:01 F:B0 ... PC:0100 PCMEM:00,C3,13,02" line per executed instruction
+pub enum Outcome {
+    //Every line of the reference matched
+    Matched(usize),
+    Diverged(Box<Divergence>),
+}
+
+pub struct Divergence {
+    //Line of the reference log, starting at 1
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+    //Matching lines just before the divergence
+    pub history: Vec<String>,
+    //Lines just after it, from the reference and from the emulator
+    pub next_expected: Vec<String>,
+    pub next_actual: Vec<String>,
+    pub instruction: disasm::Line,
+    //16 byte rows around PC
+    pub memory: Vec<(u16, Vec<u8>)>,
+}
+
+pub fn compare(emulator: &mut Emulator, reference: impl BufRead, context: usize) -> io::Result<Outcome> {
+    let mut lines = reference.lines().enumerate();
+    let mut history = VecDeque::with_capacity(context + 1);
+    let mut matched = 0;
+    while let Some((index, expected)) = lines.next() {
+        let expected = expected?.trim().to_string();
+        if expected.is_empty() {
+            continue;
+        }
+        let mut failed = false;
+        let mut actual = match next_instruction(emulator) {
+            Ok(()) => emulator.cpu.trace_line(),
+            Err(message) => {
+                failed = true;
+                message
+            }
+        };
+        if actual == expected {
+            if let Err(message) = execute(emulator) {
+                actual = format!("crashed: {}", message);
+                failed = true;
+            }
+        }
+        if actual != expected {
+            let pc = emulator.cpu.pc();
+            let read = |address: u16| emulator.cpu.bus.bus_read(address);
+            let instruction = disasm::decode(read, pc);
+            let start = (pc & 0xFFF0).saturating_sub(0x10);
+            let memory = (0..3u16)
+                .map_while(|row| start.checked_add(row * 0x10))
+                .map(|row| (row, (0..0x10).map(|i| read(row.wrapping_add(i))).collect()))
+                .collect();
+            let mut next_expected = Vec::new();
+            for (_, line) in lines.by_ref().take(context) {
+                next_expected.push(line?.trim().to_string());
+            }
+            let mut next_actual = Vec::new();
+            if !failed && execute(emulator).is_ok() {
+                while next_actual.len() < context {
+                    if next_instruction(emulator).is_err() {
+                        break;
+                    }
+                    next_actual.push(emulator.cpu.trace_line());
+                    if execute(emulator).is_err() {
+                        break;
+                    }
+                }
+            }
+            return Ok(Outcome::Diverged(Box::new(Divergence {
+                line: index + 1,
+                expected,
+                actual,
+                history: history.into(),
+                next_expected,
+                next_actual,
+                instruction,
+                memory,
+            })));
+        }
+        history.push_back(actual);
+        if history.len() > context {
+            history.pop_front();
+        }
+        matched += 1;
+    }
+    Ok(Outcome::Matched(matched))
+}
+
+//Dispatch a pending interrupt and sit out HALT, which retires no instruction and
+//so has no line in the reference while the CPU waits
+fn next_instruction(emulator: &mut Emulator) -> Result<(), String> {
+    let start = emulator.cpu.bus.cycles;
+    emulator.service_interrupts();
+    while emulator.cpu.is_halted {
+        //With IE clear nothing can ever wake it, don't wait for the timeout
+        if emulator.cpu.bus.interrupts.enable == 0 || emulator.cpu.bus.cycles - start > HALT_TIMEOUT {
+            return Err("stuck in HALT, no interrupt woke the CPU".to_string());
+        }
+        execute(emulator)?;
+        emulator.service_interrupts();
+    }
+    Ok(())
+}
+
+//Run one instruction, turning a panic of the emulator into an error
+fn execute(emulator: &mut Emulator) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        emulator.execute_instruction();
+    }))
+    .map_err(|payload| {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "panic".to_string())
+    })
+}
+
+//Names of the "key:value" fields that differ between two trace lines
+pub fn differing_fields(expected: &str, actual: &str) -> Vec<String> {
+    let fields = |line: &str| -> Vec<(String, String)> {
+        line.split_whitespace()
+            .filter_map(|field| field.split_once(':'))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let actual = fields(actual);
+    fields(expected)
+        .into_iter()
+        .filter(|(key, value)| !actual.iter().any(|(k, v)| k == key && v == value))
+        .map(|(key, _)| key)
+        .collect()
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Divergence at line {} of the reference", self.line)?;
+        let first = self.line.saturating_sub(self.history.len());
+        for (i, line) in self.history.iter().enumerate() {
+            writeln!(f, "  {:>8}  {}", first + i, line)?;
+        }
+        writeln!(f, "  expected  {}", self.expected)?;
+        writeln!(f, "  actual    {}", self.actual)?;
+        let fields = differing_fields(&self.expected, &self.actual);
+        if !fields.is_empty() {
+            writeln!(f, "  differs   {}", fields.join(", "))?;
+        }
+        writeln!(f, "\nInstruction\n  {}", self.instruction)?;
+        writeln!(f, "\nMemory")?;
+        for (address, bytes) in &self.memory {
+            let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            writeln!(f, "  {:04X}: {}", address, bytes.join(" "))?;
+        }
+        if !self.next_expected.is_empty() || !self.next_actual.is_empty() {
+            writeln!(f, "\nNext instructions")?;
+            for line in &self.next_expected {
+                writeln!(f, "  expected  {}", line)?;
+            }
+            for line in &self.next_actual {
+                writeln!(f, "  actual    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{self, Cartridge};
+    use crate::mbc::RtcClock;
+
+    #[test]
+    fn lists_differing_fields() {
+        let expected = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02";
+        let actual = "A:01 F:80 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFC PC:0100 PCMEM:00,C3,13,02";
+        assert_eq!(differing_fields(expected, actual), ["F", "SP"]);
+    }
+
+    #[test]
+    fn stops_at_first_divergence() {
+        //The blank cartridge is all NOPs from 0x100
+        let mut emulator = Emulator::new(Cartridge::blank(), RtcClock::Cycles).unwrap();
+        let regs = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE";
+        let reference = format!(
+            "{regs} PC:0100 PCMEM:00,00,00,00\n{regs} PC:0101 PCMEM:00,00,00,00\n\
+             {regs} PC:0103 PCMEM:00,00,00,00\n{regs} PC:0104 PCMEM:00,00,00,00\n"
+        );
+        let Outcome::Diverged(divergence) = compare(&mut emulator, reference.as_bytes(), 1).unwrap() else {
+            panic!("the reference should diverge");
+        };
+        assert_eq!(divergence.line, 3);
+        assert_eq!(divergence.history, [format!("{regs} PC:0101 PCMEM:00,00,00,00")]);
+        assert_eq!(divergence.instruction.address, 0x0102);
+        assert_eq!(divergence.next_actual, [format!("{regs} PC:0103 PCMEM:00,00,00,00")]);
+        assert_eq!(differing_fields(&divergence.expected, &divergence.actual), ["PC"]);
+    }
+
+    #[test]
+    fn halt_has_no_line_of_its_own() {
+        //EI, HALT, then the timer interrupt jumps to 0x0050
+        let mut rom = vec![0; 0x8000];
+        rom[0x0100..0x0102].copy_from_slice(&[0xFB, 0x76]);
+        rom[0x014D] = cartridge::header_checksum(&rom);
+        let mut emulator = Emulator::new(Cartridge::from_bytes(rom).unwrap(), RtcClock::Cycles).unwrap();
+        emulator.cpu.bus.interrupts.enable = 0x04;
+        emulator.cpu.bus.timer_write(0xFF05, 0xF0);
+        emulator.cpu.bus.timer_write(0xFF07, 0x05);
+        let regs = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D";
+        let reference = format!(
+            "{regs} SP:FFFE PC:0100 PCMEM:FB,76,00,00\n{regs} SP:FFFE PC:0101 PCMEM:76,00,00,00\n\
+             {regs} SP:FFFC PC:0050 PCMEM:00,00,00,00\n{regs} SP:FFFC PC:0051 PCMEM:00,00,00,00\n"
+        );
+        assert!(matches!(compare(&mut emulator, reference.as_bytes(), 1).unwrap(), Outcome::Matched(4)));
+    }
+
+    #[test]
+    fn halt_without_interrupt_is_reported() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0100] = 0x76;
+        rom[0x014D] = cartridge::header_checksum(&rom);
+        let mut emulator = Emulator::new(Cartridge::from_bytes(rom).unwrap(), RtcClock::Cycles).unwrap();
+        let regs = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE";
+        let reference = format!("{regs} PC:0100 PCMEM:76,00,00,00\n{regs} PC:0101 PCMEM:00,00,00,00\n");
+        let Outcome::Diverged(divergence) = compare(&mut emulator, reference.as_bytes(), 1).unwrap() else {
+            panic!("the CPU never wakes up");
+        };
+        assert_eq!(divergence.line, 2);
+        assert!(divergence.actual.starts_with("stuck in HALT"));
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use emulator::headless::HeadlessRunner;
+use emulator::Emulator;
+
+//Runs a Blargg test ROM and returns what it printed on the serial port
+fn run(relative: &str, max_frames: u64) -> Option<String> {
+    let mut emulator = common::load(relative)?;
+    let runner = HeadlessRunner {
+        frames: Some(max_frames),
+        ..HeadlessRunner::new()
+    };
+    runner
+        .run_until(&mut emulator, |emulator| {
+            let output = String::from_utf8_lossy(emulator.serial_output());
+            output.contains("Passed") || output.contains("Failed")
+        })
+        .unwrap();
+    Some(String::from_utf8_lossy(emulator.serial_output()).into_owned())
+}
+
+//Later ROMs report through cartridge RAM instead: status at 0xA000 (0x80 while running,
+//0 once passed), the DE B0 61 signature at 0xA001 and the text from 0xA004
+fn run_memory(relative: &str, max_frames: u64) -> Option<(u8, String)> {
+    let mut emulator = common::load(relative)?;
+    let runner = HeadlessRunner {
+        frames: Some(max_frames),
+        ..HeadlessRunner::new()
+    };
+    let signed = |emulator: &Emulator| {
+        (0xA001..=0xA003).map(|a| emulator.cpu.bus.bus_read(a)).eq([0xDE, 0xB0, 0x61])
+    };
+    runner
+        .run_until(&mut emulator, |emulator| {
+            signed(emulator) && emulator.cpu.bus.bus_read(0xA000) != 0x80
+        })
+        .unwrap();
+    if !signed(&emulator) {
+        return Some((0xFF, "no result at 0xA000".to_string()));
+    }
+    let text: Vec<u8> = (0xA004..0xC000)
+        .map(|a| emulator.cpu.bus.bus_read(a))
+        .take_while(|&b| b != 0)
+        .collect();
+    Some((emulator.cpu.bus.bus_read(0xA000), String::from_utf8_lossy(&text).into_owned()))
+}
+
+fn assert_passed(relative: &str, max_frames: u64) {
+    if let Some(output) = run(relative, max_frames) {
+        assert!(output.contains("Passed"), "{} did not pass, serial output:\n{}", relative, output);
+    }
+}
+
+#[test]
+fn cpu_instrs() {
+    assert_passed("blargg/cpu_instrs/cpu_instrs.gb", 4000);
+}
+
+#[test]
+fn instr_timing() {
+    assert_passed("blargg/instr_timing/instr_timing.gb", 600);
+}
+
+#[test]
+fn mem_timing() {
+    assert_passed("blargg/mem_timing/mem_timing.gb", 600);
+}
+
+#[test]
+fn mem_timing_2() {
+    if let Some((status, output)) = run_memory("blargg/mem_timing-2/mem_timing.gb", 600) {
+        assert_eq!(status, 0, "mem_timing-2 did not pass, output:\n{}", output);
+    }
+}
@@ -0,0 +1,29 @@
+#![allow(dead_code)]
+use std::env;
+use std::path::{Path, PathBuf};
+use emulator::mbc::RtcClock;
+use emulator::{launch, Emulator};
+
+//Test ROMs are not distributed with the emulator, point GB_TEST_ROMS at a copy
+//or put them in test-roms/ at the root of the project
+pub fn rom_dir() -> PathBuf {
+    env::var_os("GB_TEST_ROMS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("test-roms"))
+}
+
+//None (with a note on stderr) when the ROM isn't there, so the suite still passes without them
+pub fn load(relative: &str) -> Option<Emulator> {
+    let path = rom_dir().join(relative);
+    if !path.exists() {
+        eprintln!("skipping {}: not found", path.display());
+        return None;
+    }
+    Some(try_load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)))
+}
+
+pub fn try_load(path: &Path) -> Result<Emulator, String> {
+    let cartridge = launch::launch(path).map_err(|e| e.to_string())?;
+    //Test ROMs must run the same way every time
+    Emulator::new(cartridge, RtcClock::Cycles).map_err(|e| e.to_string())
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use emulator::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use emulator::headless::HeadlessRunner;
+use emulator::screenshot;
+
+//The picture is complete well before this
+const FRAMES: u64 = 60;
+
+//Matching pixels are shown faded, mismatches in red
+fn diff_image(actual: &[u8], expected: &[u8]) -> Vec<u8> {
+    actual
+        .iter()
+        .zip(expected)
+        .flat_map(|(&a, &e)| {
+            if a == e {
+                let grey = 0xC0 + (3 - a) * 0x15;
+                [grey, grey, grey]
+            } else {
+                [0xFF, 0x00, 0x00]
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn dmg_acid2() {
+    let Some(mut emulator) = common::load("dmg-acid2/dmg-acid2.gb") else { return };
+    let reference = common::rom_dir().join("dmg-acid2/reference-dmg.png");
+    let expected = screenshot::read_png(&reference)
+        .unwrap_or_else(|e| panic!("{}: {}", reference.display(), e));
+
+    let runner = HeadlessRunner {
+        frames: Some(FRAMES),
+        ..HeadlessRunner::new()
+    };
+    //Report a crash of the emulator as such rather than as a harness failure
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| runner.run(&mut emulator).unwrap())) {
+        let msg = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "panic".to_string());
+        panic!("the emulator panicked before frame {}: {}", FRAMES, msg);
+    }
+
+    let actual = emulator.framebuffer();
+    let mismatches = actual.iter().zip(&expected).filter(|(a, e)| a != e).count();
+    if mismatches > 0 {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+        let diff = dir.join("dmg-acid2-diff.png");
+        let output = dir.join("dmg-acid2-actual.png");
+        screenshot::write_rgb_png(&diff, SCREEN_WIDTH, SCREEN_HEIGHT, &diff_image(actual, &expected)).unwrap();
+        screenshot::write(&output, actual, &screenshot::GREY).unwrap();
+        panic!(
+            "{} pixels differ from the reference, see {} and {}",
+            mismatches,
+            diff.display(),
+            output.display()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use emulator::headless::HeadlessRunner;
+use emulator::Emulator;
+
+//Mooneye tests run for a few seconds at most
+const MAX_FRAMES: u64 = 600;
+
+//Every DMG acceptance test is in one of the two lists, a test missing from both fails the run.
+//They follow what the emulator implements, move a test when a run disagrees.
+
+//Tests expected to pass, a failure in one of them is a regression
+const EXPECTED_PASS: &[&str] = &[
+    "bits/mem_oam",
+    "bits/reg_f",
+    "boot_regs-dmgABC",
+    "di_timing-GS",
+    "div_timing",
+    "ei_sequence",
+    "ei_timing",
+    "halt_ime0_ei",
+    "halt_ime0_nointr_timing",
+    "halt_ime1_timing",
+    "halt_ime1_timing2-GS",
+    "if_ie_registers",
+    "instr/daa",
+    "interrupts/ie_push",
+    "intr_timing",
+    "oam_dma/basic",
+    "ppu/intr_1_2_timing-GS",
+    "ppu/intr_2_0_timing",
+    "ppu/intr_2_mode0_timing",
+    "ppu/intr_2_mode3_timing",
+    "ppu/vblank_stat_intr-GS",
+    "rapid_di_ei",
+    "reti_intr_timing",
+    "timer/div_write",
+    "timer/tim00",
+    "timer/tim01",
+    "timer/tim10",
+    "timer/tim11",
+];
+
+//Tests that can't pass yet, with the missing feature
+const DMA_TIMED: &str = "timed with OAM DMA, which blocks the bus from the FF46 write, one M-cycle early";
+const KNOWN_FAILURES: &[(&str, &str)] = &[
+    ("add_sp_e_timing", DMA_TIMED),
+    ("bits/unused_hwio-GS", "unmapped I/O below FF40 reads 0 instead of FF"),
+    ("boot_div-dmgABCmgb", "DIV starts at 0 instead of its value after the boot ROM"),
+    ("boot_hwio-dmgABCmgb", "there are no APU registers and unmapped I/O reads 0"),
+    ("call_cc_timing", DMA_TIMED),
+    ("call_cc_timing2", DMA_TIMED),
+    ("call_timing", DMA_TIMED),
+    ("call_timing2", DMA_TIMED),
+    ("jp_cc_timing", DMA_TIMED),
+    ("jp_timing", DMA_TIMED),
+    ("ld_hl_sp_e_timing", DMA_TIMED),
+    ("oam_dma/reg_read", "FF46 reads 0 instead of the last value written"),
+    ("oam_dma/sources-GS", "echo RAM reads 0, so transfers from 0xE000 and up copy zeros"),
+    ("oam_dma_restart", "FF46 writes are dropped while a transfer runs"),
+    ("oam_dma_start", "the bus is blocked from the FF46 write, one M-cycle before the transfer starts"),
+    ("oam_dma_timing", "the bus is blocked from the FF46 write, one M-cycle before the transfer starts"),
+    ("pop_timing", DMA_TIMED),
+    ("ppu/hblank_ly_scx_timing-GS", "mode 3 always lasts 172 dots, SCX doesn't lengthen it"),
+    ("ppu/intr_2_mode0_timing_sprites", "mode 3 always lasts 172 dots, sprites don't lengthen it"),
+    ("ppu/intr_2_oam_ok_timing", "OAM isn't locked during modes 2 and 3"),
+    ("ppu/lcdon_timing-GS", "turning the LCD on doesn't restart the line timing"),
+    ("ppu/lcdon_write_timing-GS", "turning the LCD on doesn't restart the line timing"),
+    ("ppu/stat_irq_blocking", "each STAT source requests its own interrupt, there is no shared STAT line"),
+    ("ppu/stat_lyc_onoff", "LY=LYC is only compared at the start of a line"),
+    ("push_timing", DMA_TIMED),
+    ("ret_cc_timing", DMA_TIMED),
+    ("ret_timing", DMA_TIMED),
+    ("reti_timing", DMA_TIMED),
+    ("rst_timing", DMA_TIMED),
+    ("serial/boot_sclk_align-dmgABCmgb", "the serial clock isn't aligned to the divider"),
+    ("timer/rapid_toggle", "TAC writes don't cause the falling edge glitch"),
+    ("timer/tim00_div_trigger", "DIV writes don't cause the falling edge glitch"),
+    ("timer/tim01_div_trigger", "DIV writes don't cause the falling edge glitch"),
+    ("timer/tim10_div_trigger", "DIV writes don't cause the falling edge glitch"),
+    ("timer/tim11_div_trigger", "DIV writes don't cause the falling edge glitch"),
+    ("timer/tima_reload", "TIMA reloads from TMA in the overflow cycle instead of one M-cycle later"),
+    ("timer/tima_write_reloading", "the TIMA reload from TMA isn't delayed by one M-cycle"),
+    ("timer/tma_write_reloading", "the TIMA reload from TMA isn't delayed by one M-cycle"),
+];
+
+//The suffix lists the models a test is meant for, G is DMG and dmgABC its later revisions.
+//Tests for DMG0, MGB, SGB or CGB only check boot state this emulator doesn't reproduce.
+fn for_other_model(name: &str) -> bool {
+    match name.rsplit_once('-') {
+        Some((_, models)) => !models.contains('G') && !models.contains("dmgABC"),
+        None => false,
+    }
+}
+
+enum Outcome {
+    Passed,
+    Failed,
+    Timeout,
+    Panicked(String),
+    LoadError(String),
+}
+
+impl Outcome {
+    fn label(&self) -> String {
+        match self {
+            Outcome::Passed => "pass".to_string(),
+            Outcome::Failed => "FAIL".to_string(),
+            Outcome::Timeout => "timeout".to_string(),
+            Outcome::Panicked(msg) => format!("panicked: {}", msg),
+            Outcome::LoadError(e) => format!("load error: {}", e),
+        }
+    }
+}
+
+//Pass is signalled with the Fibonacci numbers in B, C, D, E, H, L before LD B,B
+fn outcome(emulator: &Emulator) -> Outcome {
+    let r = &emulator.cpu.registers;
+    if [r.b, r.c, r.d, r.e, r.h, r.l] == [3, 5, 8, 13, 21, 34] {
+        Outcome::Passed
+    } else {
+        Outcome::Failed
+    }
+}
+
+fn run(path: &Path) -> Outcome {
+    let mut emulator = match common::try_load(path) {
+        Ok(emulator) => emulator,
+        Err(e) => return Outcome::LoadError(e),
+    };
+    let runner = HeadlessRunner {
+        frames: Some(MAX_FRAMES),
+        ..HeadlessRunner::new()
+    };
+    //One ROM crashing the emulator shouldn't take the whole table down
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        runner.run_until(&mut emulator, |emulator| emulator.cpu.breakpoint).unwrap();
+    }));
+    if let Err(payload) = result {
+        let msg = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "panic".to_string());
+        return Outcome::Panicked(msg);
+    }
+    if emulator.cpu.breakpoint {
+        outcome(&emulator)
+    } else {
+        Outcome::Timeout
+    }
+}
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if path.extension().is_some_and(|e| e == "gb") {
+            roms.push(path);
+        }
+    }
+}
+
+#[test]
+fn acceptance() {
+    let dir = common::rom_dir().join("mooneye/acceptance");
+    let mut roms = Vec::new();
+    find_roms(&dir, &mut roms);
+    if roms.is_empty() {
+        eprintln!("skipping {}: no ROMs found", dir.display());
+        return;
+    }
+    roms.sort();
+
+    let mut passed = 0;
+    let mut regressions = Vec::new();
+    let mut unlisted = Vec::new();
+    println!("{:<50} result", "test");
+    //Panics are reported in the table instead
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    for rom in &roms {
+        let name = rom.strip_prefix(&dir).unwrap().with_extension("").display().to_string();
+        if for_other_model(&name) {
+            println!("{:<50} skipped, other model", name);
+            continue;
+        }
+        let outcome = run(rom);
+        let known = KNOWN_FAILURES.iter().find(|(n, _)| *n == name);
+        match known {
+            Some((_, reason)) => println!("{:<50} {} (known: {})", name, outcome.label(), reason),
+            None => println!("{:<50} {}", name, outcome.label()),
+        }
+        let expected = EXPECTED_PASS.contains(&name.as_str());
+        match outcome {
+            Outcome::Passed => passed += 1,
+            _ if expected => regressions.push(name.clone()),
+            _ => (),
+        }
+        if !expected && known.is_none() {
+            unlisted.push(name);
+        }
+    }
+    panic::set_hook(hook);
+    println!("{}/{} passed", passed, roms.len());
+    assert!(regressions.is_empty(), "tests that used to pass now fail: {:?}", regressions);
+    assert!(unlisted.is_empty(), "tests in neither EXPECTED_PASS nor KNOWN_FAILURES: {:?}", unlisted);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use serde::Deserialize;
+use emulator::cpu::{Access, CPU};
+use emulator::register::FlagsRegister;
+
+//One entry of the SM83 single step tests (github.com/SingleStepTests/sm83)
+#[derive(Deserialize)]
+struct Test {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    //One entry per M-cycle: [address, value, pins], pins "r-m" for a read and "-wm" for a write
+    cycles: Vec<serde_json::Value>,
+}
+
+//Reads and writes of the expected cycles, internal cycles have neither pin set
+fn expected_accesses(cycles: &[serde_json::Value]) -> Vec<Access> {
+    cycles
+        .iter()
+        .filter_map(|cycle| {
+            let address = cycle.get(0)?.as_u64()? as u16;
+            let val = cycle.get(1)?.as_u64()? as u8;
+            match cycle.get(2)?.as_str()? {
+                pins if pins.starts_with('r') => Some(Access::Read(address, val)),
+                pins if pins.contains('w') => Some(Access::Write(address, val)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    sp: u16,
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    ime: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+fn load_state(cpu: &mut CPU, state: &State) {
+    let r = &mut cpu.registers;
+    r.a = state.a;
+    r.b = state.b;
+    r.c = state.c;
+    r.d = state.d;
+    r.e = state.e;
+    r.f = FlagsRegister::from(state.f);
+    r.h = state.h;
+    r.l = state.l;
+    cpu.set_pc(state.pc);
+    cpu.set_sp(state.sp);
+    cpu.set_ime(state.ime == Some(1));
+    for &(address, val) in &state.ram {
+        cpu.bus.bus_write(address, val);
+    }
+}
+
+//Every difference between the CPU and the expected state
+fn compare(cpu: &CPU, expected: &State) -> Vec<String> {
+    let r = &cpu.registers;
+    let mut mismatches = Vec::new();
+    let mut check = |name: &str, actual: u16, expected: u16| {
+        if actual != expected {
+            mismatches.push(format!("{}: {:#06x}, expected {:#06x}", name, actual, expected));
+        }
+    };
+    check("A", r.a as u16, expected.a as u16);
+    check("F", u8::from(r.f) as u16, expected.f as u16);
+    check("B", r.b as u16, expected.b as u16);
+    check("C", r.c as u16, expected.c as u16);
+    check("D", r.d as u16, expected.d as u16);
+    check("E", r.e as u16, expected.e as u16);
+    check("H", r.h as u16, expected.h as u16);
+    check("L", r.l as u16, expected.l as u16);
+    check("PC", cpu.pc(), expected.pc);
+    check("SP", cpu.sp(), expected.sp);
+    if let Some(ime) = expected.ime {
+        check("IME", cpu.ime() as u16, ime as u16);
+    }
+    for &(address, val) in &expected.ram {
+        check(&format!("({:#06x})", address), cpu.bus.bus_read(address) as u16, val as u16);
+    }
+    mismatches
+}
+
+fn run(test: &Test) -> Vec<String> {
+    let mut cpu = CPU::flat();
+    load_state(&mut cpu, &test.initial);
+    cpu.bus.accesses.clear();
+    let start = cpu.bus.cycles;
+    if panic::catch_unwind(AssertUnwindSafe(|| cpu.step())).is_err() {
+        return vec!["panicked".to_string()];
+    }
+    let m_cycles = (cpu.bus.cycles - start) / 4;
+    let mut mismatches = compare(&cpu, &test.expected);
+    if m_cycles != test.cycles.len() as u64 {
+        mismatches.push(format!("cycles: {}, expected {}", m_cycles, test.cycles.len()));
+    }
+    let expected = expected_accesses(&test.cycles);
+    if cpu.bus.accesses != expected {
+        mismatches.push(format!("accesses: {:x?}, expected {:x?}", cpu.bus.accesses, expected));
+    }
+    mismatches
+}
+
+#[test]
+fn single_step() {
+    let dir = common::rom_dir().join("sm83/v1");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        eprintln!("skipping {}: not found", dir.display());
+        return;
+    };
+    let mut files: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    files.sort();
+
+    //Panics are reported as failures, keep them off the output
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut failing = Vec::new();
+    for file in &files {
+        let opcode = file.file_stem().unwrap().to_string_lossy().into_owned();
+        let json = fs::read_to_string(file).unwrap();
+        let tests: Vec<Test> = serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {}", file.display(), e));
+        let mut failed = 0;
+        let mut first = None;
+        for test in &tests {
+            let mismatches = run(test);
+            if !mismatches.is_empty() {
+                failed += 1;
+                first.get_or_insert_with(|| format!("{}: {}", test.name, mismatches.join(", ")));
+            }
+        }
+        if let Some(first) = first {
+            println!("{:<6} {}/{} failed, first: {}", opcode, failed, tests.len(), first);
+            failing.push(opcode);
+        }
+    }
+    panic::set_hook(hook);
+    println!("{}/{} opcodes passed", files.len() - failing.len(), files.len());
+    assert!(failing.is_empty(), "failing opcodes: {}", failing.join(" "));
+}